@@ -1,8 +1,10 @@
 //! Implementations of cryptographic attacks against block ciphers.
 
+use attacks::padding_oracle::PaddingOracle;
 use utils::data::Data;
 use utils::metrics;
-use victims::block::{EcbOrCbc, EcbWithSuffix, EcbWithAffixes, EcbUserProfile, CbcCookie};
+use victims::block::{EcbOrCbc, EcbWithSuffix, EcbWithAffixes, EcbUserProfile, CbcCookie,
+                     CbcPaddingOracle};
 
 /// Determine whether a block cipher is using ECB or CBC mode.
 ///
@@ -251,4 +253,45 @@ pub fn craft_cbc_admin_token(cbc_cookie_box: &CbcCookie) -> Data {
         bytes[*position] ^= 1;
     }
     Data::from_bytes(bytes)
-}
\ No newline at end of file
+}
+
+/// Decrypt a CBC ciphertext using a padding oracle.
+///
+/// Given a black box which reveals whether a ciphertext and IV decrypt to correctly padded
+/// plaintext under CBC mode, decrypt the given ciphertext, which was encrypted using the given IV.
+///
+/// This recovers each block separately, by pairing it with a forged preceding block (or IV) and
+/// watching the padding oracle's response as the forged block is changed.
+pub fn padding_oracle_decrypt(oracle: &CbcPaddingOracle, ciphertext: &Data, iv: &Data) -> Data {
+    PaddingOracle::new(|iv: &Data, ciphertext: &Data| oracle.padding_valid(ciphertext, iv))
+        .decrypt(iv, ciphertext)
+        .unwrap()
+}
+
+/// Forge a CBC ciphertext using a padding oracle.
+///
+/// Given a black box which reveals whether a ciphertext and IV decrypt to correctly padded
+/// plaintext under CBC mode, create an IV and ciphertext which decrypt to the given message, even
+/// though the key is unknown. Returns the IV and the ciphertext, in that order.
+pub fn padding_oracle_encrypt(oracle: &CbcPaddingOracle, message: &Data) -> (Data, Data) {
+    PaddingOracle::new(|iv: &Data, ciphertext: &Data| oracle.padding_valid(ciphertext, iv))
+        .encrypt(message)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use attacks::block::padding_oracle_encrypt;
+    use utils::data::Data;
+    use victims::block::CbcPaddingOracle;
+
+    #[test]
+    fn padding_oracle_forgery() {
+        let oracle = CbcPaddingOracle::new();
+        let message = Data::from_text("comment1=cooking%20MCs;admin=true;comment2=bacon");
+        let (iv, ciphertext) = padding_oracle_encrypt(&oracle, &message);
+        assert!(oracle.check_forgery(&ciphertext, &iv, &message));
+        assert!(!oracle.check_forgery(&ciphertext, &iv, &Data::from_text("admin=false")));
+    }
+}
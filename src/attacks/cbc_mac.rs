@@ -0,0 +1,142 @@
+//! Implementations of cryptographic attacks against CBC-MAC.
+
+use utils::block::Algorithms;
+use utils::data::Data;
+use utils::padding;
+use victims::cbc_mac::{CbcMacTransfers, CbcMacTransferLists, TARGET_AMOUNT};
+
+/// Forge a transfer request for a CBC-MAC API where the IV is sent with the request.
+///
+/// Given a black box which signs transfers from the attacker's account, create a valid request
+/// for a transfer of 1M spacebucks from the victim's account to the attacker's account.
+pub fn forge_transfer_with_iv(api: &CbcMacTransfers) -> Data {
+
+    // Get the client to sign a transfer of the right amount from the attacker to themselves.
+    let request = api.sign_transfer(api.attacker_account(), TARGET_AMOUNT);
+    let message_len = request.len() - 32;
+    let mut bytes = request.bytes().to_vec();
+
+    // The first block of the message is XORed with the IV before it is encrypted, so any change
+    // to the first block can be cancelled out by making the same change to the IV. The account
+    // numbers have the same length, so changing the source account only affects the first block.
+    let forged_from = format!("from={}", api.victim_account());
+    for (ix, &byte) in forged_from.as_bytes().iter().enumerate() {
+        bytes[message_len + ix] ^= bytes[ix] ^ byte;
+        bytes[ix] = byte;
+    }
+
+    Data::from_bytes(bytes)
+}
+
+/// Forge a transfer request for a CBC-MAC API with a fixed IV, by length extension.
+///
+/// Given a black box which signs lists of transfers from the attacker's account, and a captured
+/// request from the victim's account, create a valid request which includes a transfer of 1M
+/// spacebucks from the victim's account to the attacker's account.
+pub fn forge_transfer_by_extension(api: &CbcMacTransferLists) -> Data {
+
+    // Get the client to sign a list of transfers to the attacker, starting with a dummy transfer
+    // which will absorb the damage done to the first block below.
+    let attacker = api.attacker_account();
+    let request = api.sign_transfers(&[(attacker, 1), (attacker, TARGET_AMOUNT)]);
+    let message = request.slice(0, request.len() - 16);
+    let mac = request.slice(request.len() - 16, request.len());
+
+    // Take the captured request from the victim, along with its padding. The CBC-MAC state after
+    // these blocks is the captured tag, so XORing the tag into the first block of our message
+    // puts the computation back in exactly the state it was in when signing our message.
+    let captured = api.captured_request();
+    let captured_message = captured.slice(0, captured.len() - 16);
+    let captured_mac = captured.slice(captured.len() - 16, captured.len());
+    let mut bytes = padding::pkcs7_pad(&captured_message, 16).bytes().to_vec();
+    let extension_start = bytes.len();
+    bytes.extend_from_slice(message.bytes());
+    for (ix, &byte) in captured_mac.bytes().iter().enumerate() {
+        bytes[extension_start + ix] ^= byte;
+    }
+
+    // The forged message has the same tag as our own message.
+    bytes.extend_from_slice(mac.bytes());
+    Data::from_bytes(bytes)
+}
+
+/// Forge a collision for CBC-MAC used as a hash function with a known key.
+///
+/// Given a reference text, a prefix chosen by the attacker and the AES key of a CBC-MAC with a
+/// zero IV, create a text starting with the prefix which has the same CBC-MAC as the reference.
+/// The reference text must be at least one block long.
+///
+/// The prefix should end by opening a line comment, such as `//` in JavaScript. The prefix is
+/// padded with spaces to a whole block, and followed by a block of filler, a crafted block and
+/// everything after the first block of the reference text, so the forgery keeps the meaning of
+/// the prefix as long as the rest of the reference text is all on its first line. The filler is
+/// chosen so that the crafted block is printable ASCII, so it contains no line breaks or other
+/// control characters that could end the comment early or corrupt the text.
+pub fn forge_collision(original: &Data, target_prefix: &Data, key: &Data) -> Result<Data, String> {
+    if original.len() < 16 {
+        return Err("The reference text must be at least one block long".to_string());
+    }
+
+    // The CBC-MAC state after the first block of the reference text is the encryption of that
+    // block, since the IV is zero. If the state after our prefix and filler is `s`, then following
+    // it with the block `s ^ first_block` puts the CBC-MAC into this same state, and from then on
+    // the rest of the reference text, including its padding, leads to the same tag.
+    let cipher = Algorithms::Aes.cipher(key)?;
+    let (first_block, rest) = original.bytes().split_at(16);
+
+    // Find the state after the prefix, padded with spaces to a whole number of blocks.
+    let mut forgery = target_prefix.bytes().to_vec();
+    while !forgery.len().is_multiple_of(16) {
+        forgery.push(b' ');
+    }
+    let mut state = vec![0; 16];
+    for block in forgery.chunks(16) {
+        for (x, y) in state.iter_mut().zip(block.iter()) {
+            *x ^= *y;
+        }
+        cipher.encrypt_block(&mut state);
+    }
+
+    // Try different blocks of printable filler, counting through them like the digits of a
+    // number, until the crafted block is printable too. Each byte is printable with probability
+    // 95/256, so this takes around ten million attempts.
+    let mut filler = [b' '; 16];
+    loop {
+        let mut crafted = [0; 16];
+        for (ix, byte) in crafted.iter_mut().enumerate() {
+            *byte = state[ix] ^ filler[ix];
+        }
+        cipher.encrypt_block(&mut crafted);
+        for (x, y) in crafted.iter_mut().zip(first_block.iter()) {
+            *x ^= *y;
+        }
+        if crafted.iter().all(|byte| (0x20..0x7f).contains(byte)) {
+            forgery.extend_from_slice(&filler);
+            forgery.extend_from_slice(&crafted);
+            forgery.extend_from_slice(rest);
+            return Ok(Data::from_bytes(forgery));
+        }
+
+        for byte in filler.iter_mut().rev() {
+            if *byte < b'~' {
+                *byte += 1;
+                break;
+            }
+            *byte = b' ';
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use attacks::cbc_mac::forge_collision;
+    use utils::data::Data;
+
+    #[test]
+    fn collision_short_original() {
+        let key = Data::from_text("YELLOW SUBMARINE");
+        let prefix = Data::from_text("alert(1);//");
+        assert!(forge_collision(&Data::from_text("alert(2);"), &prefix, &key).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Implementations of cryptographic attacks against CTR mode.
+
+use attacks::xor::best_single_byte_key;
+use utils::data::Data;
+use utils::metrics::score_as_english;
+use victims::ctr::RandomAccessCtr;
+
+/// Decrypt a CTR-encrypted message using an exposed edit function.
+///
+/// Given a black box which exposes a CTR-encrypted ciphertext and allows the underlying plaintext
+/// to be edited at any offset, determine the plaintext.
+pub fn break_random_access_ctr(ctr_box: &RandomAccessCtr) -> Data {
+
+    // Editing the plaintext to be equal to the ciphertext itself XORs the ciphertext with the
+    // keystream a second time, which gives back the original plaintext.
+    let ciphertext = ctr_box.ciphertext();
+    ctr_box.edit(&ciphertext, 0, &ciphertext).unwrap()
+}
+
+/// Find the keystream used to encrypt several messages under CTR mode with a fixed nonce.
+///
+/// Given ciphertexts which were all encrypted under the same key and nonce, and so were XORed
+/// with the same keystream, determine as much of the keystream as they cover.
+pub fn break_fixed_nonce(ciphertexts: &[Data]) -> Data {
+
+    // Reusing the keystream means that the bytes at each position in the ciphertexts have all
+    // been XORed with the same key byte, just like a column of repeating-key XOR. Up to the length
+    // of the shortest ciphertext, every column is full, and each one can be solved as
+    // single-byte XOR. Beyond that, the columns are made up of the remaining, longer ciphertexts,
+    // so the results get less reliable as fewer ciphertexts are left.
+    let max_len = ciphertexts.iter().map(|ciphertext| ciphertext.len()).max().unwrap_or(0);
+    let mut keystream = Vec::with_capacity(max_len);
+    for ix in 0..max_len {
+        let column: Vec<u8> = ciphertexts.iter()
+            .filter(|ciphertext| ciphertext.len() > ix)
+            .map(|ciphertext| ciphertext.bytes()[ix])
+            .collect();
+        let (key, _) = best_single_byte_key(&Data::from_bytes(column));
+        keystream.push(key.bytes().first().cloned().unwrap_or(0));
+    }
+
+    Data::from_bytes(keystream)
+}
+
+/// Common trigrams in English text, used to judge a guess by the letters around it.
+const COMMON_TRIGRAMS: [&[u8]; 40] =
+    [b"the", b"and", b"ing", b"ion", b"tio", b"ent", b"ati", b"for", b"her", b"ter", b"hat",
+     b"tha", b"ere", b"ate", b"his", b"con", b"res", b"ver", b"all", b"ons", b"nce", b"men",
+     b"ith", b"ted", b"ers", b"pro", b"thi", b"wit", b"are", b"ess", b"not", b"ive", b"was",
+     b"ect", b"rea", b"com", b"eve", b"per", b"int", b"est"];
+
+/// The maximum number of passes made over the keystream when substituting guesses.
+const SUBSTITUTION_PASSES: usize = 10;
+
+/// Returns a score for a single character of a line of text, given whether it starts or ends the
+/// line - a higher score is better.
+fn score_character(byte: u8, start: bool, end: bool) -> f64 {
+    let mut score = score_as_english(&Data::from_single_byte(byte));
+    match byte {
+        b'a'..=b'z' if start => score -= 5.0,
+        b' ' if start || end => score -= 20.0,
+        b'a'..=b'z' | b'A'..=b'Z' | b' ' | b',' | b'.' | b';' | b':' | b'!' | b'?' | b'\'' |
+        b'-' | b'"' => {}
+        _ => score -= 20.0,
+    }
+    score
+}
+
+/// Returns a score for a pair of adjacent characters in a line of text - a higher score is better.
+fn score_pair(first: u8, second: u8) -> f64 {
+    match (first, second) {
+        (b' ', b' ') => -20.0,
+        (b'a'..=b'z', b'A'..=b'Z') => -20.0,
+        (b',', b'a'..=b'z') | (b',', b'A'..=b'Z') | (b'.', b'a'..=b'z') | (b';', b'a'..=b'z') |
+        (b':', b'a'..=b'z') => -10.0,
+        _ => 0.0,
+    }
+}
+
+/// Returns a score for the guess of the keystream byte at the given position, judged by the
+/// characters it gives and how they fit with the characters around them - a higher score is
+/// better.
+fn score_in_context(ciphertexts: &[Data], keystream: &[u8], ix: usize) -> f64 {
+    let mut score = 0.0;
+    for ciphertext in ciphertexts.iter().filter(|ciphertext| ciphertext.len() > ix) {
+
+        // Decrypt the two characters either side of this one, where the line has them.
+        let start = ix.saturating_sub(2);
+        let end = (ix + 3).min(ciphertext.len());
+        let window: Vec<u8> = (start..end)
+            .map(|jx| ciphertext.bytes()[jx] ^ keystream[jx])
+            .collect();
+        let pos = ix - start;
+
+        score += score_character(window[pos], ix == 0, ix + 1 == ciphertext.len());
+        if pos > 0 {
+            score += score_pair(window[pos - 1], window[pos]);
+        }
+        if pos + 1 < window.len() {
+            score += score_pair(window[pos], window[pos + 1]);
+        }
+        for trigram in window.windows(3) {
+            if COMMON_TRIGRAMS.contains(&&trigram.to_ascii_lowercase()[..]) {
+                score += 10.0;
+            }
+        }
+    }
+    score
+}
+
+/// Find the keystream used to encrypt several messages under CTR mode with a fixed nonce, by
+/// guessing and substituting one byte at a time.
+///
+/// Given ciphertexts which were all encrypted under the same key and nonce, determine as much of
+/// the keystream as they cover. Each byte of the keystream is judged by whether the characters it
+/// gives make sense alongside the characters around them, which still works near the end of the
+/// longer ciphertexts, where there are too few of them for letter frequencies alone.
+pub fn break_fixed_nonce_by_substitution(ciphertexts: &[Data]) -> Data {
+
+    // Start from the statistical guess, which is good wherever there are many ciphertexts.
+    let mut keystream = break_fixed_nonce(ciphertexts).bytes().to_vec();
+
+    // Substitute each byte of the keystream in turn with the guess which makes the most sense in
+    // context. Each change can make a difference to the bytes on either side of it, so keep
+    // going until nothing changes.
+    for _ in 0..SUBSTITUTION_PASSES {
+        let mut changed = false;
+        for ix in 0..keystream.len() {
+            let original = keystream[ix];
+            let (mut best_guess, mut best_score) = (original, f64::NEG_INFINITY);
+            for guess in 0..=255 {
+                keystream[ix] = guess;
+                let score = score_in_context(ciphertexts, &keystream, ix);
+                if score > best_score {
+                    best_guess = guess;
+                    best_score = score;
+                }
+            }
+            changed |= best_guess != original;
+            keystream[ix] = best_guess;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    Data::from_bytes(keystream)
+}
+
@@ -1,4 +1,8 @@
 //! Implementations of cryptographic attacks.
 
 pub mod block;
+pub mod cbc_mac;
+pub mod ctr;
+pub mod padding_oracle;
+pub mod timing;
 pub mod xor;
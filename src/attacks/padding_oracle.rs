@@ -0,0 +1,302 @@
+//! A generic padding oracle attack against CBC mode, which can be used against any oracle.
+//!
+//! The attack only needs a function which takes an IV and a ciphertext, and returns whether they
+//! decrypt to correctly padded plaintext. It is not tied to any particular black box, so it can be
+//! pointed at anything from a victim struct to a test harness wrapping a local service.
+
+use std::cell::Cell;
+use std::error;
+use std::fmt;
+
+use utils::block::PaddingSchemes;
+use utils::data::Data;
+use utils::padding;
+
+/// Errors that can arise when running a padding oracle attack.
+pub enum PaddingOracleError {
+    /// The padding scheme is not supported by the attack.
+    Padding,
+    /// The IV or ciphertext was of an invalid size.
+    DataLength,
+    /// The oracle did not accept any guess for a byte, so it is not behaving as a padding oracle
+    /// for the configured block size and padding scheme.
+    Oracle,
+}
+
+impl fmt::Display for PaddingOracleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::PaddingOracleError::*;
+        match *self {
+            Padding => write!(f, "The padding scheme is not supported by the attack"),
+            DataLength => write!(f, "The given data has an invalid length"),
+            Oracle => write!(f, "The oracle did not accept any guess for a byte"),
+        }
+    }
+}
+
+impl fmt::Debug for PaddingOracleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self, f)
+    }
+}
+
+impl error::Error for PaddingOracleError {
+    fn description(&self) -> &str {
+        use self::PaddingOracleError::*;
+        match *self {
+            Padding => "unsupported padding scheme",
+            DataLength => "invalid data length",
+            Oracle => "inconsistent oracle",
+        }
+    }
+}
+
+/// A padding oracle attack against CBC mode.
+///
+/// The oracle is called with an IV and a ciphertext, in that order, and must return whether they
+/// decrypt to plaintext with valid padding. By default the block size is 16 bytes and the padding
+/// is PKCS#7; ISO/IEC 7816-4 padding is also supported. The number of queries made to the oracle
+/// is counted across all decryptions.
+pub struct PaddingOracle<F: Fn(&Data, &Data) -> bool> {
+    /// The padding oracle.
+    oracle: F,
+    /// The block size of the cipher.
+    block_size: usize,
+    /// The padding scheme that the oracle checks.
+    padding: PaddingSchemes,
+    /// The number of queries made to the oracle so far.
+    queries: Cell<usize>,
+}
+
+impl<F: Fn(&Data, &Data) -> bool> PaddingOracle<F> {
+    /// Creates a new PaddingOracle which attacks the given oracle, assuming a block size of 16
+    /// bytes and PKCS#7 padding.
+    pub fn new(oracle: F) -> PaddingOracle<F> {
+        PaddingOracle {
+            oracle,
+            block_size: 16,
+            padding: PaddingSchemes::Pkcs7,
+            queries: Cell::new(0),
+        }
+    }
+
+    /// Sets the block size of the cipher used by the oracle.
+    pub fn with_block_size(mut self, block_size: usize) -> PaddingOracle<F> {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Sets the padding scheme checked by the oracle, which must be PKCS#7 or ISO/IEC 7816-4.
+    pub fn with_padding(mut self, padding: PaddingSchemes) -> PaddingOracle<F> {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns the number of queries made to the oracle so far.
+    pub fn queries(&self) -> usize {
+        self.queries.get()
+    }
+
+    /// Queries the oracle, keeping count of the number of queries.
+    fn query(&self, iv: &[u8], ciphertext: &Data) -> bool {
+        self.queries.set(self.queries.get() + 1);
+        (self.oracle)(&Data::from_bytes(iv.to_vec()), ciphertext)
+    }
+
+    /// Returns the plaintext bytes which make the end of a block valid padding starting from the
+    /// given position, or `None` if the padding scheme is not supported.
+    fn padding_target(&self, pos: usize) -> Option<Vec<u8>> {
+        let pad = self.block_size - pos;
+        match self.padding {
+            PaddingSchemes::Pkcs7 => Some(vec![pad as u8; pad]),
+            PaddingSchemes::Iso7816 => {
+                let mut target = vec![0; pad];
+                target[0] = 0x80;
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether valid padding for the byte at the given position could instead be caused
+    /// by the bytes before it, so that a hit needs to be confirmed.
+    fn ambiguous(&self, pos: usize) -> bool {
+        match self.padding {
+            // Only the last byte is ambiguous, as "\x01" could also be the end of "\x02\x02".
+            PaddingSchemes::Pkcs7 => pos > 0 && pos == self.block_size - 1,
+            // Any byte after the first is ambiguous, as a zero byte could be preceded by 0x80.
+            _ => pos > 0,
+        }
+    }
+
+    /// Decrypts a single block of ciphertext using the oracle, returning the raw output of the
+    /// block cipher, before it is XORed with the previous block or the IV.
+    pub fn decrypt_block(&self, block: &Data) -> Result<Data, PaddingOracleError> {
+        if block.len() != self.block_size {
+            return Err(PaddingOracleError::DataLength);
+        }
+
+        // Work backwards through the block, using a forged IV to set each byte of the plaintext in
+        // turn to the start of the padding, and the bytes after it to the rest of the padding.
+        let mut output = vec![0; self.block_size];
+        for pos in (0..self.block_size).rev() {
+            let target = self.padding_target(pos).ok_or(PaddingOracleError::Padding)?;
+            let mut forged_iv = vec![0; self.block_size];
+            for ix in pos + 1..self.block_size {
+                forged_iv[ix] = output[ix] ^ target[ix - pos];
+            }
+
+            let mut found = false;
+            for guess in 0..=255 {
+                forged_iv[pos] = guess;
+                if !self.query(&forged_iv, block) {
+                    continue;
+                }
+
+                // If the padding could have come from the earlier bytes, change the byte before
+                // this one and check that the padding is still valid.
+                if self.ambiguous(pos) {
+                    forged_iv[pos - 1] ^= 1;
+                    let valid = self.query(&forged_iv, block);
+                    forged_iv[pos - 1] ^= 1;
+                    if !valid {
+                        continue;
+                    }
+                }
+
+                output[pos] = guess ^ target[0];
+                found = true;
+                break;
+            }
+            if !found {
+                return Err(PaddingOracleError::Oracle);
+            }
+        }
+
+        Ok(Data::from_bytes(output))
+    }
+
+    /// Decrypts the given ciphertext, which was encrypted using the given IV, and removes the
+    /// padding.
+    pub fn decrypt(&self, iv: &Data, ciphertext: &Data) -> Result<Data, PaddingOracleError> {
+        if iv.len() != self.block_size || ciphertext.is_empty() ||
+           !ciphertext.len().is_multiple_of(self.block_size) {
+            return Err(PaddingOracleError::DataLength);
+        }
+
+        // Decrypt each block using the oracle, and XOR the result with the previous ciphertext
+        // block, or with the IV for the first block, to get the plaintext.
+        let mut plaintext = Vec::with_capacity(ciphertext.len());
+        let mut previous = iv.bytes();
+        for block in ciphertext.bytes().chunks(self.block_size) {
+            let output = self.decrypt_block(&Data::from_bytes(block.to_vec()))?;
+            plaintext.extend(output.bytes().iter().zip(previous.iter()).map(|(x, y)| x ^ y));
+            previous = block;
+        }
+
+        // Finally, remove the padding, which the oracle has already told us is valid.
+        padding::unpad(&Data::from_bytes(plaintext), self.block_size, &self.padding)
+            .map_err(|_| PaddingOracleError::Oracle)
+    }
+
+    /// Forges a ciphertext which decrypts to the given plaintext, using the oracle as a
+    /// decryption primitive (the CBC-R technique). Returns the IV and the ciphertext.
+    ///
+    /// The forgery is built backwards from a random last block. Decrypting a block with the
+    /// oracle gives the intermediate state that the previous block is XORed with, so choosing the
+    /// previous block to be that state XORed with the desired plaintext fixes the plaintext, and
+    /// the previous block is then decrypted in turn. The IV is the last block to be chosen.
+    pub fn encrypt(&self, plaintext: &Data) -> Result<(Data, Data), PaddingOracleError> {
+        let padded = padding::pad(plaintext, self.block_size, &self.padding)
+            .map_err(|_| PaddingOracleError::Padding)?;
+
+        let mut blocks = vec![Data::random(self.block_size)];
+        for chunk in padded.bytes().chunks(self.block_size).rev() {
+            let intermediate = self.decrypt_block(&blocks[0])?;
+            let previous = intermediate.bytes().iter().zip(chunk.iter()).map(|(x, y)| x ^ y);
+            blocks.insert(0, Data::from_bytes(previous.collect()));
+        }
+
+        let iv = blocks.remove(0);
+        let ciphertext = blocks.iter().flat_map(|block| block.bytes().to_vec()).collect();
+        Ok((iv, Data::from_bytes(ciphertext)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use attacks::padding_oracle::PaddingOracle;
+    use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes};
+    use utils::data::Data;
+
+    #[test]
+    fn iso_7816_with_small_blocks() {
+        let key = Data::random(8);
+        let iv = Data::random(8);
+        let plaintext = Data::random(45);
+        let ciphertext = BlockCipher::new(Algorithms::Des,
+                                          OperationModes::Cbc(iv.clone()),
+                                          PaddingSchemes::Iso7816,
+                                          &key)
+            .unwrap()
+            .encrypt(&plaintext)
+            .unwrap();
+
+        let oracle = |iv: &Data, ciphertext: &Data| {
+            BlockCipher::new(Algorithms::Des,
+                             OperationModes::Cbc(iv.clone()),
+                             PaddingSchemes::Iso7816,
+                             &key)
+                .unwrap()
+                .decrypt(ciphertext)
+                .is_ok()
+        };
+        let attack = PaddingOracle::new(oracle)
+            .with_block_size(8)
+            .with_padding(PaddingSchemes::Iso7816);
+        let recovered = attack.decrypt(&iv, &ciphertext).unwrap();
+        assert_eq!(recovered.to_hex(), plaintext.to_hex());
+
+        // Each byte takes at most 256 guesses, plus a query to check each ambiguous hit.
+        assert!(attack.queries() > 0);
+        assert!(attack.queries() <= ciphertext.len() * (256 + 8));
+    }
+
+    #[test]
+    fn cbc_r_forgery() {
+        let key = Data::random(16);
+        let oracle = |iv: &Data, ciphertext: &Data| {
+            BlockCipher::new(Algorithms::Aes,
+                             OperationModes::Cbc(iv.clone()),
+                             PaddingSchemes::Pkcs7,
+                             &key)
+                .unwrap()
+                .decrypt(ciphertext)
+                .is_ok()
+        };
+        let attack = PaddingOracle::new(oracle);
+
+        // The victim decrypts the forgery, under its own key and the forged IV, to our message.
+        let message = Data::from_text("comment1=cooking%20MCs;admin=true;comment2=bacon");
+        let (iv, ciphertext) = attack.encrypt(&message).unwrap();
+        assert_eq!(ciphertext.len(), 64);
+        let victim = BlockCipher::new(Algorithms::Aes,
+                                      OperationModes::Cbc(iv),
+                                      PaddingSchemes::Pkcs7,
+                                      &key)
+            .unwrap();
+        assert_eq!(victim.decrypt(&ciphertext).unwrap().to_text(), message.to_text());
+    }
+
+    #[test]
+    fn unsupported_configurations() {
+        let attack = PaddingOracle::new(|_: &Data, _: &Data| true)
+            .with_padding(PaddingSchemes::Zero);
+        assert!(attack.decrypt(&Data::random(16), &Data::random(32)).is_err());
+        assert!(attack.decrypt(&Data::random(16), &Data::random(20)).is_err());
+        assert!(attack.decrypt(&Data::random(8), &Data::random(32)).is_err());
+        assert!(attack.decrypt_block(&Data::random(8)).is_err());
+        assert_eq!(attack.queries(), 0);
+    }
+}
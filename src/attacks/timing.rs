@@ -0,0 +1,121 @@
+//! Implementations of attacks which use the time taken by a service to respond.
+
+use std::cell::RefCell;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use attacks::padding_oracle::PaddingOracle;
+use utils::data::Data;
+
+/// The number of requests used to measure the typical response time for each kind of token.
+const CALIBRATION_SAMPLES: usize = 25;
+
+/// The number of times in a row that a token must be slow to check to count as correctly padded.
+const CONFIRMATION_SAMPLES: usize = 5;
+
+/// A client for a token-checking service, which classifies tokens as correctly padded or not by
+/// how long the service takes to respond.
+///
+/// Noise, such as other processes competing for the CPU, can only ever slow a response down, so
+/// the fastest of several responses is the best estimate of the work done by the service. The
+/// client first calibrates itself by timing a genuine token, which is correctly padded, and random
+/// tokens, which almost never are, and sets a threshold halfway between their fastest response
+/// times. A token which takes longer than this to check is timed several more times, and only
+/// counts as correctly padded if every response is above the threshold.
+pub struct TimingOracleClient {
+    /// The connection to the service, for reading responses.
+    reader: RefCell<BufReader<TcpStream>>,
+    /// The connection to the service, for sending requests.
+    writer: TcpStream,
+    /// The response time above which a token is considered to be correctly padded.
+    threshold: Duration,
+}
+
+impl TimingOracleClient {
+    /// Connects to the service at the given address, and calibrates the client using the given
+    /// genuine token.
+    pub fn connect(address: SocketAddr,
+                   ciphertext: &Data,
+                   iv: &Data)
+                   -> io::Result<TimingOracleClient> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut client = TimingOracleClient {
+            reader: RefCell::new(BufReader::new(stream.try_clone()?)),
+            writer: stream,
+            threshold: Duration::from_secs(0),
+        };
+
+        let mut fast = Duration::MAX;
+        let mut slow = Duration::MAX;
+        for _ in 0..CALIBRATION_SAMPLES {
+            fast = fast.min(client.time(&Data::random(16), &Data::random(16))?);
+            slow = slow.min(client.time(ciphertext, iv)?);
+        }
+        client.threshold = (fast + slow) / 2;
+        Ok(client)
+    }
+
+    /// Sends the given token to the service, and returns how long it took to respond.
+    fn time(&self, ciphertext: &Data, iv: &Data) -> io::Result<Duration> {
+        let request = format!("{}{}\n", iv.to_hex(), ciphertext.to_hex());
+        let mut response = String::new();
+        let start = Instant::now();
+        (&self.writer).write_all(request.as_bytes())?;
+        self.reader.borrow_mut().read_line(&mut response)?;
+        Ok(start.elapsed())
+    }
+
+    /// Returns whether the given token appears to be correctly padded, judging by how long the
+    /// service takes to check it.
+    pub fn padding_valid(&self, ciphertext: &Data, iv: &Data) -> io::Result<bool> {
+        for _ in 0..CONFIRMATION_SAMPLES {
+            if self.time(ciphertext, iv)? <= self.threshold {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Decrypt a token using a padding oracle which is only visible through timing.
+///
+/// Given the address of a service which checks CBC-encrypted tokens and responds in the same way
+/// to bad padding and a bad MAC, but takes longer to respond when the padding is correct, decrypt
+/// the given token, which was encrypted using the given IV. Returns the message in the token,
+/// without the 16-byte tag at the end.
+pub fn timing_padding_oracle_decrypt(address: SocketAddr,
+                                     ciphertext: &Data,
+                                     iv: &Data)
+                                     -> Result<Data, String> {
+    let client = TimingOracleClient::connect(address, ciphertext, iv)
+        .map_err(|err| format!("{}", err))?;
+
+    // The padding oracle interface has no way to report errors, so a lost connection is treated
+    // as bad padding, which leads to an error from the attack.
+    let attack = PaddingOracle::new(|iv: &Data, ciphertext: &Data| {
+        client.padding_valid(ciphertext, iv).unwrap_or(false)
+    });
+    let plaintext = attack.decrypt(iv, ciphertext).map_err(|err| format!("{}", err))?;
+    if plaintext.len() < 16 {
+        return Err("The token is too short to contain a tag".to_string());
+    }
+    Ok(plaintext.slice(0, plaintext.len() - 16))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use attacks::timing::timing_padding_oracle_decrypt;
+    use victims::timing::TimingPaddingServer;
+
+    #[test]
+    fn timing_padding_oracle() {
+        let server = TimingPaddingServer::start().unwrap();
+        let (ciphertext, iv) = server.token();
+        let message = timing_padding_oracle_decrypt(server.address(), &ciphertext, &iv).unwrap();
+        assert!(server.check_answer(&message));
+    }
+}
@@ -1,27 +1,18 @@
 //! Helper functions which are useful in solving the challenges but too large to sensibly be
 //! inline in the solution itself.
 
-use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes, DecryptError};
+use utils::block::DecryptError;
 use utils::data::Data;
+use utils::padding;
 
 /// Pad the given `Data` to the given block size using PKCS#7
 pub fn pkcs7_pad(data: &Data, block_size: usize) -> Data {
-    let block = BlockCipher::new(Algorithms::Null(block_size),
-                                 OperationModes::Ecb,
-                                 PaddingSchemes::Pkcs7,
-                                 &Data::new())
-        .unwrap();
-    block.encrypt(data).unwrap()
+    padding::pkcs7_pad(data, block_size)
 }
 
-/// Unpad the given `Data` using PKCS#7
+/// Unpad the given `Data`, padded to the AES block size of 16 bytes, using PKCS#7
 pub fn pkcs7_unpad(data: &Data) -> Result<Data, DecryptError> {
-    let block = BlockCipher::new(Algorithms::Null(1),
-                                 OperationModes::Ecb,
-                                 PaddingSchemes::Pkcs7,
-                                 &Data::new())
-        .unwrap();
-    block.decrypt(data)
+    padding::pkcs7_unpad(data, 16)
 }
 
 /// Add the given padding to some text and return the result as a `Data`
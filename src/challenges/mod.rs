@@ -9,6 +9,9 @@
 pub mod helpers;
 pub mod set1;
 pub mod set2;
+pub mod set3;
+pub mod set4;
+pub mod set7;
 
 use ansi_term::Colour::{Cyan, Green};
 
@@ -0,0 +1,222 @@
+//! Solutions to the challenges in Set 3.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use attacks;
+use challenges::{ChallengeResults, ChallengeResultsBuilder};
+use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes, CounterFormats};
+use utils::data::Data;
+use utils::xor;
+use victims::block::CbcPaddingOracle;
+
+/// Run the solution to Set 3 Challenge 17 (The CBC padding oracle)
+///
+/// # Outputs
+///
+/// `text_out` - The secret string recovered using the padding oracle, as a plain text string.
+///
+/// `success` - Whether the secret string was recovered correctly.
+pub fn challenge17() -> ChallengeResults {
+
+    // Create a padding oracle black-box, and get the encrypted secret from it.
+    let oracle = CbcPaddingOracle::new();
+    let (ciphertext, iv) = oracle.encrypt();
+
+    // Decrypt the secret using the padding oracle.
+    let secret = attacks::block::padding_oracle_decrypt(&oracle, &ciphertext, &iv);
+
+    // Check that we've been successful.
+    let success = oracle.check_answer(&secret);
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(3)
+        .challenge(17)
+        .description("The CBC padding oracle")
+        .output("text_out", &secret.to_text())
+        .output("success", &format!("{}", success))
+        .finalize()
+}
+
+/// Run the solution to Set 3 Challenge 18 (Implement CTR, the stream cipher mode)
+///
+/// # Outputs
+///
+/// `base64_in` - The encrypted input as a base 64 string.
+///
+/// `text_key` - The key as a plain text string.
+///
+/// `hex_nonce` - The nonce as a hexadecimal string.
+///
+/// `text_out` - The decrypted output as a plain text string.
+pub fn challenge18() -> ChallengeResults {
+
+    // Get the base-64 input.
+    let base64_in = "L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==";
+    let data = Data::from_base64(base64_in).unwrap();
+
+    // Get the key and nonce.
+    let text_key = "YELLOW SUBMARINE";
+    let key = Data::from_text(text_key);
+    let hex_nonce = "0000000000000000";
+    let nonce = Data::from_hex(hex_nonce).unwrap();
+
+    // Decrypt the data using AES-128-CTR with a little-endian 64-bit counter.
+    let mode = OperationModes::Ctr {
+        nonce,
+        counter_format: CounterFormats::LittleEndian64,
+    };
+    let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key).unwrap();
+    let text_out = block.decrypt(&data).unwrap().to_text();
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(3)
+        .challenge(18)
+        .description("Implement CTR, the stream cipher mode")
+        .output("base64_in", base64_in)
+        .output("text_key", text_key)
+        .output("hex_nonce", hex_nonce)
+        .output("text_out", &text_out)
+        .finalize()
+}
+
+/// Encrypt each of the base-64 encoded lines in the given file under AES-CTR with a random key and
+/// a fixed nonce, and then decrypt them again using the given attack to recover the keystream.
+///
+/// Returns the decrypted lines, and whether each of them was recovered correctly up to the last
+/// position which at least `min_lines` of the lines reach. Beyond this, there are too few lines
+/// left to be sure of the keystream. If `ignore_case` is set, the case of letters is not checked.
+fn break_fixed_nonce_file(path: &str,
+                          attack: fn(&[Data]) -> Data,
+                          min_lines: usize,
+                          ignore_case: bool)
+                          -> (String, bool) {
+
+    // Read in the plaintexts from file.
+    let file = File::open(Path::new(path)).unwrap();
+    let reader = BufReader::new(file);
+    let plaintexts: Vec<Data> = reader.lines()
+        .map(|line| Data::from_base64(&line.unwrap()).unwrap())
+        .collect();
+
+    // Encrypt each of them using the same key and a nonce of zero.
+    let mode = OperationModes::Ctr {
+        nonce: Data::from_bytes(vec![0; 8]),
+        counter_format: CounterFormats::LittleEndian64,
+    };
+    let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &Data::random(16))
+        .unwrap();
+    let ciphertexts: Vec<Data> = plaintexts.iter()
+        .map(|plaintext| block.encrypt(plaintext).unwrap())
+        .collect();
+
+    // Recover the keystream, and use it to decrypt the ciphertexts.
+    let keystream = attack(&ciphertexts);
+    let decrypted: Vec<Data> = ciphertexts.iter()
+        .map(|ciphertext| xor::xor(ciphertext, &keystream))
+        .collect();
+
+    // Check each decryption up to the length reached by the given number of lines.
+    let mut lengths: Vec<usize> = plaintexts.iter().map(|plaintext| plaintext.len()).collect();
+    lengths.sort_by(|x, y| y.cmp(x));
+    let check_len = lengths[min_lines.min(lengths.len()) - 1];
+    let success = plaintexts.iter().zip(decrypted.iter()).all(|(plaintext, guess)| {
+        let len = plaintext.len().min(check_len);
+        let (expected, actual) = (&plaintext.bytes()[..len], &guess.bytes()[..len]);
+        if ignore_case {
+            expected.eq_ignore_ascii_case(actual)
+        } else {
+            expected == actual
+        }
+    });
+    let text_out: Vec<String> = decrypted.iter()
+        .map(|guess| String::from_utf8_lossy(guess.bytes()).into_owned())
+        .collect();
+
+    (text_out.join("\n"), success)
+}
+
+/// Run the solution to Set 3 Challenge 19 (Break fixed-nonce CTR mode using substitutions)
+///
+/// # Outputs
+///
+/// `text_out` - The decrypted lines as plain text, one per line.
+///
+/// `success` - Whether the lines were decrypted exactly, up to the last position which at least
+/// three of them reach.
+pub fn challenge19() -> ChallengeResults {
+
+    // Guess each byte of the keystream in turn, and substitute whichever guess makes the most
+    // sense alongside the characters around it.
+    let attack = attacks::ctr::break_fixed_nonce_by_substitution;
+    let (text_out, success) = break_fixed_nonce_file("input/set3challenge19.txt", attack, 3, false);
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(3)
+        .challenge(19)
+        .description("Break fixed-nonce CTR mode using substitutions")
+        .output("text_out", &text_out)
+        .output("success", &format!("{}", success))
+        .finalize()
+}
+
+/// Run the solution to Set 3 Challenge 20 (Break fixed-nonce CTR statistically)
+///
+/// # Outputs
+///
+/// `text_out` - The decrypted lines as plain text, one per line.
+///
+/// `success` - Whether the lines were decrypted correctly up to the last position which at least
+/// eight of them reach, ignoring the case of letters, which letter frequencies cannot determine.
+pub fn challenge20() -> ChallengeResults {
+
+    // Break the keystream column by column, as for repeating-key XOR.
+    let attack = attacks::ctr::break_fixed_nonce;
+    let (text_out, success) = break_fixed_nonce_file("input/set3challenge20.txt", attack, 8, true);
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(3)
+        .challenge(20)
+        .description("Break fixed-nonce CTR statistically")
+        .output("text_out", &text_out)
+        .output("success", &format!("{}", success))
+        .finalize()
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn challenge17() {
+        let results = super::challenge17();
+        results.check_prefix("text_out", "00000");
+        results.check("success", "true");
+    }
+
+    #[test]
+    fn challenge18() {
+        let results = super::challenge18();
+        results.check("base64_in",
+                      "L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==");
+        results.check("text_key", "YELLOW SUBMARINE");
+        results.check("hex_nonce", "0000000000000000");
+        results.check("text_out", "Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby ");
+    }
+
+    #[test]
+    fn challenge19() {
+        let results = super::challenge19();
+        results.check("success", "true");
+    }
+
+    #[test]
+    fn challenge20() {
+        let results = super::challenge20();
+        results.check("success", "true");
+    }
+}
@@ -0,0 +1,68 @@
+//! Solutions to the challenges in Set 4.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use attacks;
+use challenges::{ChallengeResults, ChallengeResultsBuilder};
+use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes};
+use utils::data::Data;
+use victims::ctr::RandomAccessCtr;
+
+/// Run the solution to Set 4 Challenge 25 (Break "random access read/write" AES CTR)
+///
+/// # Outputs
+///
+/// `success` - Whether the correct plaintext was obtained.
+///
+/// `text_out` - The decrypted plaintext as a plain text string.
+pub fn challenge25() -> ChallengeResults {
+
+    // Get the plaintext by decrypting the input to Set 1 Challenge 7.
+    let mut base64_in = "".to_string();
+    let file = File::open(Path::new("input/set1challenge7.txt")).unwrap();
+    let reader = BufReader::new(file);
+    for line_it in reader.lines() {
+        base64_in.push_str(&line_it.unwrap());
+    }
+    let data = Data::from_base64(&base64_in).unwrap();
+    let key = Data::from_text("YELLOW SUBMARINE");
+    let block = BlockCipher::new(Algorithms::Aes,
+                                 OperationModes::Ecb,
+                                 PaddingSchemes::Pkcs7,
+                                 &key)
+        .unwrap();
+    let plaintext = block.decrypt(&data).unwrap();
+
+    // Create a random-access CTR black-box.
+    let ctr_box = RandomAccessCtr::new(plaintext);
+
+    // Decode the plaintext without reading it directly!
+    let plaintext_guess = attacks::ctr::break_random_access_ctr(&ctr_box);
+    let success = ctr_box.check_answer(&plaintext_guess);
+    let text_out = plaintext_guess.to_text();
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(4)
+        .challenge(25)
+        .description("Break \"random access read/write\" AES CTR")
+        .output("success", &format!("{}", success))
+        .output("text_out", &text_out)
+        .finalize()
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn challenge25() {
+        let results = super::challenge25();
+        results.check("success", "true");
+        results.check_prefix("text_out",
+                             "I'm back and I'm ringin' the bell \n\
+                              A rockin' on the mike while the fly girls yell \n\
+                              In ecstasy in the back of me");
+    }
+}
@@ -0,0 +1,110 @@
+//! Solutions to the challenges in Set 7.
+
+use attacks;
+use challenges::{ChallengeResults, ChallengeResultsBuilder};
+use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes};
+use utils::data::Data;
+use victims::cbc_mac::{CbcMacTransfers, CbcMacTransferLists};
+
+/// Run the solution to Set 7 Challenge 49 (CBC-MAC Message Forgery)
+///
+/// # Outputs
+///
+/// `iv_success` - Whether a transfer was forged against the API which sends the IV with each
+/// request.
+///
+/// `extension_success` - Whether a transfer was forged against the API with a fixed IV, using
+/// length extension.
+pub fn challenge49() -> ChallengeResults {
+
+    // Forge a transfer by manipulating the IV sent with the request.
+    let api = CbcMacTransfers::new();
+    let forged_request = attacks::cbc_mac::forge_transfer_with_iv(&api);
+    let iv_success = api.check_answer(&forged_request);
+
+    // Forge a transfer by extending a captured request from the victim.
+    let api = CbcMacTransferLists::new();
+    let forged_request = attacks::cbc_mac::forge_transfer_by_extension(&api);
+    let extension_success = api.check_answer(&forged_request);
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(7)
+        .challenge(49)
+        .description("CBC-MAC Message Forgery")
+        .output("iv_success", &format!("{}", iv_success))
+        .output("extension_success", &format!("{}", extension_success))
+        .finalize()
+}
+
+/// Run the solution to Set 7 Challenge 50 (Hashing with CBC-MAC)
+///
+/// # Outputs
+///
+/// `hash_in` - The CBC-MAC hash of the original JavaScript snippet, as a hex string.
+///
+/// `hash_out` - The CBC-MAC hash of the forged JavaScript snippet, as a hex string.
+///
+/// `text_out` - The forged JavaScript snippet, as a plain text string.
+///
+/// `success` - Whether the forged snippet starts with the chosen code and has the same hash as
+/// the original, with the rest of it hidden in a comment made up of printable characters.
+pub fn challenge50() -> ChallengeResults {
+
+    // The hash is the CBC-MAC with a zero IV and a known key.
+    let key = Data::from_text("YELLOW SUBMARINE");
+    let block = BlockCipher::new(Algorithms::Aes,
+                                 OperationModes::Cbc(Data::from_bytes(vec![0; 16])),
+                                 PaddingSchemes::Pkcs7,
+                                 &key)
+        .unwrap();
+    let hash = |text: &Data| {
+        let ciphertext = block.encrypt(text).unwrap();
+        ciphertext.slice(ciphertext.len() - 16, ciphertext.len())
+    };
+
+    // Forge a snippet which runs different code but has the same hash.
+    let original = Data::from_text("alert('MZA who was that?');\n");
+    let prefix = Data::from_text("alert('Ayo, the Wu is back!');//");
+    let forgery = attacks::cbc_mac::forge_collision(&original, &prefix, &key).unwrap();
+    let hash_in = hash(&original).to_hex();
+    let hash_out = hash(&forgery).to_hex();
+    let text_out = forgery.to_text();
+
+    // Check that everything after the chosen code is hidden in the comment, which contains no
+    // control characters.
+    let hidden = &text_out[prefix.len()..text_out.len() - 1];
+    let success = hash_in == hash_out && text_out.starts_with(&prefix.to_text()) &&
+                  hidden.chars().all(|c| (' '..='~').contains(&c));
+
+    // Return the results
+    ChallengeResultsBuilder::new()
+        .set(7)
+        .challenge(50)
+        .description("Hashing with CBC-MAC")
+        .output("hash_in", &hash_in)
+        .output("hash_out", &hash_out)
+        .output("text_out", &text_out)
+        .output("success", &format!("{}", success))
+        .finalize()
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn challenge49() {
+        let results = super::challenge49();
+        results.check("iv_success", "true");
+        results.check("extension_success", "true");
+    }
+
+    #[test]
+    fn challenge50() {
+        let results = super::challenge50();
+        results.check("hash_in", "296b8d7cb78a243dda4d0a61d33bbdd1");
+        results.check("hash_out", "296b8d7cb78a243dda4d0a61d33bbdd1");
+        results.check_prefix("text_out", "alert('Ayo, the Wu is back!');//");
+        results.check("success", "true");
+    }
+}
@@ -31,4 +31,17 @@ fn main() {
     println!("{}", challenges::set2::challenge14());
     println!("{}", challenges::set2::challenge15());
     println!("{}", challenges::set2::challenge16());
+
+    // Run the challenges in Set 3.
+    println!("{}", challenges::set3::challenge17());
+    println!("{}", challenges::set3::challenge18());
+    println!("{}", challenges::set3::challenge19());
+    println!("{}", challenges::set3::challenge20());
+
+    // Run the challenges in Set 4.
+    println!("{}", challenges::set4::challenge25());
+
+    // Run the challenges in Set 7.
+    println!("{}", challenges::set7::challenge49());
+    println!("{}", challenges::set7::challenge50());
 }
@@ -6,6 +6,7 @@ use std::fmt;
 use std::error;
 
 use utils::block::Cipher;
+use utils::block::aesni::AesNi;
 
 // Lookup tables for use in the AES encryption, decryption and key-schedule algorithms.
 
@@ -593,6 +594,9 @@ pub struct AesCipher {
     pub round_keys: Vec<u32>,
     /// The round keys used for dceryption of a block of data.
     pub inv_round_keys: Vec<u32>,
+    /// The AES-NI backend, if the processor supports it. When present, it is used in place of
+    /// the lookup tables to encrypt and decrypt blocks.
+    aesni: Option<AesNi>,
 }
 
 /// Create a word from four individual bytes.
@@ -602,8 +606,16 @@ fn word_from_bytes(byte1: u8, byte2: u8, byte3: u8, byte4: u8) -> u32 {
 
 impl AesCipher {
     /// Creates a new AesCipher which uses the given key for encryption and decryption of blocks
-    /// of data.
+    /// of data. The AES-NI instructions are used if the processor supports them.
     pub fn new(key: &[u8]) -> Result<AesCipher, AesKeyError> {
+        let mut aes = AesCipher::new_portable(key)?;
+        aes.aesni = AesNi::new(&aes.round_keys, &aes.inv_round_keys);
+        Ok(aes)
+    }
+
+    /// Creates a new AesCipher which uses the given key, and always uses the portable lookup
+    /// tables to encrypt and decrypt blocks, even if the processor supports AES-NI.
+    pub fn new_portable(key: &[u8]) -> Result<AesCipher, AesKeyError> {
 
         // Check that the key is a supported length, and initialize some useful variables.
         let (key_words, num_rounds) = match key.len() {
@@ -658,107 +670,99 @@ impl AesCipher {
         Ok(AesCipher {
             round_keys: round_keys,
             inv_round_keys: inv_round_keys,
+            aesni: None,
         })
     }
 }
 
 impl Cipher for AesCipher {
-    /// Encrypt a single block of 16 bytes using the AES algorithm.
-    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
-
-        // Copy the input into the state array. Also create a dummy state array to hold temporary
-        // results during the rounds.
-        let mut dummy = vec![0, 0, 0, 0];
-        let mut state = Vec::with_capacity(4);
+    /// Encrypt a single block of 16 bytes in place using the AES algorithm.
+    fn encrypt_block(&self, block: &mut [u8]) {
 
-        for ix in 0..4 {
-            state.push(word_from_bytes(input[4 * ix],
-                                       input[4 * ix + 1],
-                                       input[4 * ix + 2],
-                                       input[4 * ix + 3]));
+        // Use the hardware instructions if they are available.
+        if let Some(ref aesni) = self.aesni {
+            aesni.encrypt_block(block);
+            return;
         }
 
-        // Do the initial round-key addition.
+        // Copy the input into the state array, doing the initial round-key addition.
+        let mut state = [0; 4];
         for (ix, word) in state.iter_mut().enumerate() {
-            *word ^= self.round_keys[ix];
+            *word = word_from_bytes(block[4 * ix],
+                                    block[4 * ix + 1],
+                                    block[4 * ix + 2],
+                                    block[4 * ix + 3]) ^ self.round_keys[ix];
         }
 
         // Perform all but the last round of encryption. Use the T-tables to run these rounds
         // quickly.
         for round in 1..(self.round_keys.len() / 4 - 1) {
-            for (ix, word) in dummy.iter_mut().enumerate() {
+            let mut next = [0; 4];
+            for (ix, word) in next.iter_mut().enumerate() {
                 *word = T0[(state[ix % 4] >> 24) as u8 as usize] ^
                         T1[(state[(ix + 1) % 4] >> 16) as u8 as usize] ^
                         T2[(state[(ix + 2) % 4] >> 8) as u8 as usize] ^
                         T3[state[(ix + 3) % 4] as u8 as usize] ^
                         self.round_keys[4 * round + ix];
             }
-            for ix in 0..4 {
-                state[ix] = dummy[ix];
-            }
+            state = next;
         }
 
         // Finally, perform the final round, which does not include a MixCols operation, and
-        // return the result in byte form.
-        let mut output = Vec::with_capacity(16);
+        // write the result back into the block.
         for ix in 0..4 {
-            let key = self.round_keys[self.round_keys.len() - 4 + ix];
-            output.push(SBOX[(state[ix % 4] >> 24) as u8 as usize] ^ (key >> 24) as u8);
-            output.push(SBOX[(state[(ix + 1) % 4] >> 16) as u8 as usize] ^ (key >> 16) as u8);
-            output.push(SBOX[(state[(ix + 2) % 4] >> 8) as u8 as usize] ^ (key >> 8) as u8);
-            output.push(SBOX[state[(ix + 3) % 4] as u8 as usize] ^ key as u8);
+            let word = word_from_bytes(SBOX[(state[ix % 4] >> 24) as u8 as usize],
+                                       SBOX[(state[(ix + 1) % 4] >> 16) as u8 as usize],
+                                       SBOX[(state[(ix + 2) % 4] >> 8) as u8 as usize],
+                                       SBOX[state[(ix + 3) % 4] as u8 as usize]) ^
+                       self.round_keys[self.round_keys.len() - 4 + ix];
+            block[4 * ix..4 * ix + 4].copy_from_slice(&word.to_be_bytes());
         }
-
-        output
     }
 
-    /// Decrypt a single block of 16 bytes using the AES algorithm.
-    fn decrypt(&self, input: &[u8]) -> Vec<u8> {
-
-        // Copy the input into the state array. Also create a dummy state array to hold
-        // temporary results during the rounds.
-        let mut dummy = vec![0, 0, 0, 0];
-        let mut state = Vec::with_capacity(4);
+    /// Decrypt a single block of 16 bytes in place using the AES algorithm.
+    fn decrypt_block(&self, block: &mut [u8]) {
 
-        for ix in 0..4 {
-            state.push(word_from_bytes(input[4 * ix],
-                                       input[4 * ix + 1],
-                                       input[4 * ix + 2],
-                                       input[4 * ix + 3]));
+        // Use the hardware instructions if they are available.
+        if let Some(ref aesni) = self.aesni {
+            aesni.decrypt_block(block);
+            return;
         }
 
-        // Do the initial round-key addition.
+        // Copy the input into the state array, doing the initial round-key addition.
+        let mut state = [0; 4];
         for (ix, word) in state.iter_mut().enumerate() {
-            *word ^= self.inv_round_keys[self.inv_round_keys.len() - 4 + ix];
+            *word = word_from_bytes(block[4 * ix],
+                                    block[4 * ix + 1],
+                                    block[4 * ix + 2],
+                                    block[4 * ix + 3]) ^
+                    self.inv_round_keys[self.inv_round_keys.len() - 4 + ix];
         }
 
         // Perform all but the last round of decryption. Use the T-tables to run these rounds
         // quickly.
         for round in 1..(self.round_keys.len() / 4 - 1) {
-            for (ix, word) in dummy.iter_mut().enumerate() {
+            let mut next = [0; 4];
+            for (ix, word) in next.iter_mut().enumerate() {
                 *word = INV_T0[(state[ix % 4] >> 24) as u8 as usize] ^
                         INV_T1[(state[(ix + 3) % 4] >> 16) as u8 as usize] ^
                         INV_T2[(state[(ix + 2) % 4] >> 8) as u8 as usize] ^
                         INV_T3[state[(ix + 1) % 4] as u8 as usize] ^
                         self.inv_round_keys[self.inv_round_keys.len() - 4 * (round + 1) + ix];
             }
-            for ix in 0..4 {
-                state[ix] = dummy[ix];
-            }
+            state = next;
         }
 
         // Finally, perform the final round, which does not include an InvMixCols operation, and
-        // return the result in byte form.
-        let mut output = Vec::with_capacity(16);
+        // write the result back into the block.
         for ix in 0..4 {
-            let key = self.inv_round_keys[ix];
-            output.push(INV_SBOX[(state[ix % 4] >> 24) as u8 as usize] ^ (key >> 24) as u8);
-            output.push(INV_SBOX[(state[(ix + 3) % 4] >> 16) as u8 as usize] ^ (key >> 16) as u8);
-            output.push(INV_SBOX[(state[(ix + 2) % 4] >> 8) as u8 as usize] ^ (key >> 8) as u8);
-            output.push(INV_SBOX[state[(ix + 1) % 4] as u8 as usize] ^ key as u8);
+            let word = word_from_bytes(INV_SBOX[(state[ix % 4] >> 24) as u8 as usize],
+                                       INV_SBOX[(state[(ix + 3) % 4] >> 16) as u8 as usize],
+                                       INV_SBOX[(state[(ix + 2) % 4] >> 8) as u8 as usize],
+                                       INV_SBOX[state[(ix + 1) % 4] as u8 as usize]) ^
+                       self.inv_round_keys[ix];
+            block[4 * ix..4 * ix + 4].copy_from_slice(&word.to_be_bytes());
         }
-
-        output
     }
 
     /// The size of a block for the AES cipher.
@@ -766,3 +770,49 @@ impl Cipher for AesCipher {
         16
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::Cipher;
+    use utils::block::aes::AesCipher;
+    use utils::data::Data;
+
+    #[test]
+    fn portable_vector() {
+        // The example vector from FIPS-197, Appendix C.1.
+        let key = Data::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let portable = AesCipher::new_portable(key.bytes()).unwrap();
+        assert!(portable.aesni.is_none());
+
+        let plaintext = Data::from_hex("00112233445566778899aabbccddeeff").unwrap();
+        let mut block = plaintext.bytes().to_vec();
+        portable.encrypt_block(&mut block);
+        assert_eq!(Data::from_bytes(block.clone()).to_hex(), "69c4e0d86a7b0430d8cdb78070b4c55a");
+        portable.decrypt_block(&mut block);
+        assert_eq!(Data::from_bytes(block).to_hex(), plaintext.to_hex());
+    }
+
+    #[test]
+    fn aesni_matches_portable() {
+        for &key_len in &[16, 24, 32] {
+            for _ in 0..50 {
+                let key = Data::random(key_len);
+                let hardware = AesCipher::new(key.bytes()).unwrap();
+                let portable = AesCipher::new_portable(key.bytes()).unwrap();
+
+                let plaintext = Data::random(16);
+                let mut expected = plaintext.bytes().to_vec();
+                let mut actual = plaintext.bytes().to_vec();
+                portable.encrypt_block(&mut expected);
+                hardware.encrypt_block(&mut actual);
+                assert_eq!(actual, expected);
+
+                portable.decrypt_block(&mut expected);
+                hardware.decrypt_block(&mut actual);
+                assert_eq!(actual, expected);
+                assert_eq!(actual, plaintext.bytes());
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Encryption and decryption using a bitsliced, constant-time implementation of AES.
+//!
+//! Unlike the lookup-table implementation in `aes.rs`, this never uses secret data as an index
+//! or as the condition of a branch, so its memory access pattern and running time do not depend
+//! on the key or the data. This makes it much slower, but immune to cache-timing attacks.
+//!
+//! The 16 bytes of the state are held as eight 16-bit bit-planes, where bit `i` of plane `j` is
+//! bit `j` of byte `i` of the state. Byte `i` of the state is in row `i % 4` and column `i / 4`.
+//! SubBytes is computed arithmetically as an inversion in GF(2^8) followed by an affine map,
+//! while ShiftRows and MixColumns become fixed permutations of the bits within each plane.
+
+use utils::block::Cipher;
+use utils::block::aes::{AesKeyError, BadAesKeyLength};
+
+/// The round constants for the key schedule.
+static RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// A 16-byte AES state, held as eight bit-planes.
+type State = [u16; 8];
+
+/// A bitsliced, constant-time AES encryptor and decryptor.
+pub struct BitslicedAesCipher {
+    /// The round keys, held as bit-planes, in the order they are used for encryption.
+    round_keys: Vec<State>,
+}
+
+impl BitslicedAesCipher {
+    /// Creates a new BitslicedAesCipher which uses the given key for encryption and decryption
+    /// of blocks of data.
+    pub fn new(key: &[u8]) -> Result<BitslicedAesCipher, AesKeyError> {
+
+        // Check that the key is a supported length, and initialize some useful variables.
+        let (key_words, num_rounds) = match key.len() {
+            16 => (4, 10),
+            24 => (6, 12),
+            32 => (8, 14),
+            _ => return Err(BadAesKeyLength),
+        };
+
+        // Perform key-expansion to generate the round keys for the cipher, one word of four
+        // bytes at a time. The S-box is applied using the bitsliced SubBytes, so that the key
+        // schedule is also free of secret-dependent lookups.
+        let mut words: Vec<[u8; 4]> = key.chunks(4).map(|w| [w[0], w[1], w[2], w[3]]).collect();
+        for ix in key_words..4 * (num_rounds + 1) {
+            let mut tmp = words[ix - 1];
+            if ix % key_words == 0 {
+                tmp = sub_word([tmp[1], tmp[2], tmp[3], tmp[0]]);
+                tmp[0] ^= RCON[ix / key_words - 1];
+            } else if key_words == 8 && ix % key_words == 4 {
+                tmp = sub_word(tmp);
+            }
+            let word = words[ix - key_words];
+            words.push([word[0] ^ tmp[0], word[1] ^ tmp[1], word[2] ^ tmp[2], word[3] ^ tmp[3]]);
+        }
+
+        // Group the words into round keys, and convert them into bit-planes.
+        let round_keys = words.chunks(4)
+            .map(|round| {
+                let mut block = [0; 16];
+                for (bytes, word) in block.chunks_mut(4).zip(round.iter()) {
+                    bytes.copy_from_slice(word);
+                }
+                to_planes(&block)
+            })
+            .collect();
+
+        Ok(BitslicedAesCipher { round_keys })
+    }
+}
+
+impl Cipher for BitslicedAesCipher {
+    /// Encrypt a single block of 16 bytes in place using the AES algorithm.
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let last = self.round_keys.len() - 1;
+        let mut state = to_planes(block);
+        add_round_key(&mut state, &self.round_keys[0]);
+        for key in &self.round_keys[1..last] {
+            sub_bytes(&mut state);
+            shift_rows(&mut state);
+            mix_columns(&mut state);
+            add_round_key(&mut state, key);
+        }
+        sub_bytes(&mut state);
+        shift_rows(&mut state);
+        add_round_key(&mut state, &self.round_keys[last]);
+        from_planes(&state, block);
+    }
+
+    /// Decrypt a single block of 16 bytes in place using the AES algorithm.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let last = self.round_keys.len() - 1;
+        let mut state = to_planes(block);
+        add_round_key(&mut state, &self.round_keys[last]);
+        for key in self.round_keys[1..last].iter().rev() {
+            inv_shift_rows(&mut state);
+            inv_sub_bytes(&mut state);
+            add_round_key(&mut state, key);
+            inv_mix_columns(&mut state);
+        }
+        inv_shift_rows(&mut state);
+        inv_sub_bytes(&mut state);
+        add_round_key(&mut state, &self.round_keys[0]);
+        from_planes(&state, block);
+    }
+
+    /// The size of a block for the AES cipher.
+    fn block_size(&self) -> usize {
+        16
+    }
+}
+
+/// Converts a block of 16 bytes into bit-planes.
+fn to_planes(block: &[u8]) -> State {
+    let mut state = [0; 8];
+    for (ix, byte) in block.iter().enumerate() {
+        for (bit, plane) in state.iter_mut().enumerate() {
+            *plane |= (((byte >> bit) & 1) as u16) << ix;
+        }
+    }
+    state
+}
+
+/// Converts bit-planes back into a block of 16 bytes.
+fn from_planes(state: &State, block: &mut [u8]) {
+    for (ix, byte) in block.iter_mut().enumerate() {
+        *byte = 0;
+        for (bit, plane) in state.iter().enumerate() {
+            *byte |= (((plane >> ix) & 1) as u8) << bit;
+        }
+    }
+}
+
+/// Applies the S-box to each byte of a single word.
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let mut block = [0; 16];
+    block[..4].copy_from_slice(&word);
+    let mut state = to_planes(&block);
+    sub_bytes(&mut state);
+    from_planes(&state, &mut block);
+    [block[0], block[1], block[2], block[3]]
+}
+
+/// XORs the round key into the state.
+fn add_round_key(state: &mut State, key: &State) {
+    for (plane, key_plane) in state.iter_mut().zip(key.iter()) {
+        *plane ^= *key_plane;
+    }
+}
+
+/// Multiplies each byte of one state by the corresponding byte of another in GF(2^8), modulo the
+/// AES polynomial x^8 + x^4 + x^3 + x + 1.
+fn gf_mul(x: &State, y: &State) -> State {
+
+    // Multiply the polynomials, giving a product of degree at most 14.
+    let mut product = [0; 15];
+    for (ix, x_plane) in x.iter().enumerate() {
+        for (jx, y_plane) in y.iter().enumerate() {
+            product[ix + jx] ^= x_plane & y_plane;
+        }
+    }
+
+    // Reduce the product, using x^8 = x^4 + x^3 + x + 1, from the top coefficient downwards.
+    for ix in (8..15).rev() {
+        let high = product[ix];
+        product[ix - 4] ^= high;
+        product[ix - 5] ^= high;
+        product[ix - 7] ^= high;
+        product[ix - 8] ^= high;
+    }
+
+    let mut result = [0; 8];
+    result.copy_from_slice(&product[..8]);
+    result
+}
+
+/// Inverts each byte of the state in GF(2^8), mapping zero to itself, by raising it to the
+/// power 254 = 2 + 4 + ... + 128.
+fn gf_inverse(state: &State) -> State {
+    let mut power = gf_mul(state, state);
+    let mut result = power;
+    for _ in 2..8 {
+        power = gf_mul(&power, &power);
+        result = gf_mul(&result, &power);
+    }
+    result
+}
+
+/// Applies the AES S-box to each byte of the state.
+fn sub_bytes(state: &mut State) {
+    let inverse = gf_inverse(state);
+
+    // Apply the affine transformation, including the addition of the constant 0x63.
+    for (bit, plane) in state.iter_mut().enumerate() {
+        *plane = inverse[bit] ^ inverse[(bit + 4) % 8] ^ inverse[(bit + 5) % 8] ^
+                 inverse[(bit + 6) % 8] ^ inverse[(bit + 7) % 8];
+        if (0x63 >> bit) & 1 == 1 {
+            *plane = !*plane;
+        }
+    }
+}
+
+/// Applies the inverse of the AES S-box to each byte of the state.
+fn inv_sub_bytes(state: &mut State) {
+
+    // Undo the affine transformation, including the addition of the constant 0x63.
+    let mut affine = [0; 8];
+    for (bit, plane) in affine.iter_mut().enumerate() {
+        *plane = state[(bit + 2) % 8] ^ state[(bit + 5) % 8] ^ state[(bit + 7) % 8];
+        if (0x05 >> bit) & 1 == 1 {
+            *plane = !*plane;
+        }
+    }
+
+    *state = gf_inverse(&affine);
+}
+
+/// Moves the bits of each plane so that byte `ix` of the result is byte `source(ix)` of the
+/// input. The permutation is fixed, so this does not depend on any secret data.
+fn permute<F: Fn(usize) -> usize>(state: &mut State, source: F) {
+    for plane in state.iter_mut() {
+        let mut permuted = 0;
+        for ix in 0..16 {
+            permuted |= ((*plane >> source(ix)) & 1) << ix;
+        }
+        *plane = permuted;
+    }
+}
+
+/// Returns a copy of the state in which each column has been rotated upwards by the given
+/// number of rows.
+fn rotate_columns(state: &State, rows: usize) -> State {
+    let mut rotated = *state;
+    permute(&mut rotated, |ix| (ix & !3) | ((ix + rows) % 4));
+    rotated
+}
+
+/// Multiplies each byte of the state by x in GF(2^8).
+fn xtime(state: &State) -> State {
+    [state[7],
+     state[0] ^ state[7],
+     state[1],
+     state[2] ^ state[7],
+     state[3] ^ state[7],
+     state[4],
+     state[5],
+     state[6]]
+}
+
+/// Applies the AES ShiftRows transformation to the state.
+fn shift_rows(state: &mut State) {
+    permute(state, |ix| (ix + 4 * (ix % 4)) % 16);
+}
+
+/// Applies the inverse of the AES ShiftRows transformation to the state.
+fn inv_shift_rows(state: &mut State) {
+    permute(state, |ix| (ix + 16 - 4 * (ix % 4)) % 16);
+}
+
+/// Applies the AES MixColumns transformation to the state, where each byte becomes
+/// `2 * a[r] + 3 * a[r + 1] + a[r + 2] + a[r + 3]` for the bytes `a` in its column.
+fn mix_columns(state: &mut State) {
+    let one = rotate_columns(state, 1);
+    let two = rotate_columns(state, 2);
+    let three = rotate_columns(state, 3);
+    let doubled = xtime(state);
+    let doubled_one = xtime(&one);
+    for ix in 0..8 {
+        state[ix] = doubled[ix] ^ doubled_one[ix] ^ one[ix] ^ two[ix] ^ three[ix];
+    }
+}
+
+/// Applies the inverse of the AES MixColumns transformation to the state.
+///
+/// This uses the factorisation of InvMixColumns as MixColumns applied after multiplying each
+/// column by the polynomial `4y^2 + 5`, which only needs multiplications by x.
+fn inv_mix_columns(state: &mut State) {
+    let two = rotate_columns(state, 2);
+    let mut sum = [0; 8];
+    for ix in 0..8 {
+        sum[ix] = state[ix] ^ two[ix];
+    }
+    let quadrupled = xtime(&xtime(&sum));
+    for ix in 0..8 {
+        state[ix] ^= quadrupled[ix];
+    }
+    mix_columns(state);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::Cipher;
+    use utils::block::aes::AesCipher;
+    use utils::block::aes_bitsliced::BitslicedAesCipher;
+    use utils::data::Data;
+
+    #[test]
+    fn fips_197_vectors() {
+        let plaintext = "00112233445566778899aabbccddeeff";
+        let vectors = [("000102030405060708090a0b0c0d0e0f", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+                       ("000102030405060708090a0b0c0d0e0f1011121314151617",
+                        "dda97ca4864cdfe06eaf70a0ec0d7191"),
+                       ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                        "8ea2b7ca516745bfeafc49904b496089")];
+        for &(key, ciphertext) in &vectors {
+            let cipher = BitslicedAesCipher::new(Data::from_hex(key).unwrap().bytes()).unwrap();
+            let mut block = Data::from_hex(plaintext).unwrap().bytes().to_vec();
+            cipher.encrypt_block(&mut block);
+            assert_eq!(Data::from_bytes(block.clone()).to_hex(), ciphertext);
+            cipher.decrypt_block(&mut block);
+            assert_eq!(Data::from_bytes(block).to_hex(), plaintext);
+        }
+    }
+
+    #[test]
+    fn matches_table_implementation() {
+        for &key_len in &[16, 24, 32] {
+            for _ in 0..20 {
+                let key = Data::random(key_len);
+                let bitsliced = BitslicedAesCipher::new(key.bytes()).unwrap();
+                let table = AesCipher::new_portable(key.bytes()).unwrap();
+                let mut expected = Data::random(16).bytes().to_vec();
+                let mut actual = expected.clone();
+                table.encrypt_block(&mut expected);
+                bitsliced.encrypt_block(&mut actual);
+                assert_eq!(actual, expected);
+                table.decrypt_block(&mut expected);
+                bitsliced.decrypt_block(&mut actual);
+                assert_eq!(actual, expected);
+            }
+        }
+        assert!(BitslicedAesCipher::new(&[0; 20]).is_err());
+    }
+}
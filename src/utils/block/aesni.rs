@@ -0,0 +1,117 @@
+//! Hardware-accelerated AES using the AES-NI instructions of x86_64 processors.
+//!
+//! The round keys are taken from the portable key expansion in `aes.rs`, so only the block
+//! encryption and decryption are done in hardware. The decryption keys produced there already
+//! have the InvMixCols transformation applied, which is the form expected by `aesdec`.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// The round keys for an AES cipher, laid out as 16-byte blocks for use by AES-NI.
+pub struct AesNi {
+    /// The round keys used for encryption, in the order they are applied.
+    round_keys: Vec<[u8; 16]>,
+    /// The round keys used for decryption, in the order they are applied.
+    inv_round_keys: Vec<[u8; 16]>,
+}
+
+/// Converts a sequence of 32-bit round-key words into 16-byte blocks, one for each round.
+fn key_blocks(words: &[u32]) -> Vec<[u8; 16]> {
+    words.chunks(4)
+        .map(|round| {
+            let mut block = [0; 16];
+            for (bytes, word) in block.chunks_mut(4).zip(round.iter()) {
+                bytes.copy_from_slice(&word.to_be_bytes());
+            }
+            block
+        })
+        .collect()
+}
+
+impl AesNi {
+    /// Returns an AES-NI backend using the given encryption and decryption round keys, as
+    /// generated by the portable key expansion, or `None` if the processor does not support the
+    /// AES-NI instructions.
+    pub fn new(round_keys: &[u32], inv_round_keys: &[u32]) -> Option<AesNi> {
+        if !AesNi::is_supported() {
+            return None;
+        }
+
+        // The decryption rounds use the inverse keys in reverse order.
+        let mut inv_round_keys = key_blocks(inv_round_keys);
+        inv_round_keys.reverse();
+
+        Some(AesNi {
+            round_keys: key_blocks(round_keys),
+            inv_round_keys,
+        })
+    }
+
+    /// Returns true if the processor supports the AES-NI instructions.
+    #[cfg(target_arch = "x86_64")]
+    fn is_supported() -> bool {
+        is_x86_feature_detected!("aes") && is_x86_feature_detected!("sse2")
+    }
+
+    /// Returns true if the processor supports the AES-NI instructions.
+    #[cfg(not(target_arch = "x86_64"))]
+    fn is_supported() -> bool {
+        false
+    }
+
+    /// Encrypts a single block of 16 bytes in place.
+    pub fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 16, "AES blocks are 16 bytes long");
+        // This is safe because an `AesNi` is only created when the processor supports AES-NI.
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            encrypt(&self.round_keys, block)
+        }
+    }
+
+    /// Decrypts a single block of 16 bytes in place.
+    pub fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 16, "AES blocks are 16 bytes long");
+        // This is safe because an `AesNi` is only created when the processor supports AES-NI.
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            decrypt(&self.inv_round_keys, block)
+        }
+    }
+}
+
+/// Encrypts a single block in place using the given round keys.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,sse2")]
+unsafe fn encrypt(round_keys: &[[u8; 16]], block: &mut [u8]) {
+    let last = round_keys.len() - 1;
+    let mut state = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    state = _mm_xor_si128(state, load_key(&round_keys[0]));
+    for key in &round_keys[1..last] {
+        state = _mm_aesenc_si128(state, load_key(key));
+    }
+    state = _mm_aesenclast_si128(state, load_key(&round_keys[last]));
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, state);
+}
+
+/// Decrypts a single block in place using the given round keys, which must be in the order in
+/// which they are applied.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,sse2")]
+unsafe fn decrypt(inv_round_keys: &[[u8; 16]], block: &mut [u8]) {
+    let last = inv_round_keys.len() - 1;
+    let mut state = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    state = _mm_xor_si128(state, load_key(&inv_round_keys[0]));
+    for key in &inv_round_keys[1..last] {
+        state = _mm_aesdec_si128(state, load_key(key));
+    }
+    state = _mm_aesdeclast_si128(state, load_key(&inv_round_keys[last]));
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, state);
+}
+
+/// Loads a round key into a vector register.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn load_key(key: &[u8; 16]) -> __m128i {
+    _mm_loadu_si128(key.as_ptr() as *const __m128i)
+}
@@ -0,0 +1,332 @@
+//! Encryption and decryption using DES and Triple-DES.
+
+pub use self::DesKeyError::*;
+
+use std::fmt;
+use std::error;
+
+use utils::block::Cipher;
+
+// Tables for use in the DES encryption, decryption and key-schedule algorithms. Each permutation
+// table lists, for each bit of its output, the position of the input bit it is taken from,
+// counting from 1 at the most significant bit.
+
+/// The initial permutation, applied to the block before the first round.
+static IP: [u8; 64] = [58, 50, 42, 34, 26, 18, 10, 2, 60, 52, 44, 36, 28, 20, 12, 4, 62, 54, 46,
+                       38, 30, 22, 14, 6, 64, 56, 48, 40, 32, 24, 16, 8, 57, 49, 41, 33, 25, 17,
+                       9, 1, 59, 51, 43, 35, 27, 19, 11, 3, 61, 53, 45, 37, 29, 21, 13, 5, 63,
+                       55, 47, 39, 31, 23, 15, 7];
+
+/// The final permutation, which is the inverse of the initial permutation.
+static FP: [u8; 64] = [40, 8, 48, 16, 56, 24, 64, 32, 39, 7, 47, 15, 55, 23, 63, 31, 38, 6, 46,
+                       14, 54, 22, 62, 30, 37, 5, 45, 13, 53, 21, 61, 29, 36, 4, 44, 12, 52, 20,
+                       60, 28, 35, 3, 43, 11, 51, 19, 59, 27, 34, 2, 42, 10, 50, 18, 58, 26, 33,
+                       1, 41, 9, 49, 17, 57, 25];
+
+/// The expansion of the 32-bit half-block to 48 bits in the round function.
+static E: [u8; 48] = [32, 1, 2, 3, 4, 5, 4, 5, 6, 7, 8, 9, 8, 9, 10, 11, 12, 13, 12, 13, 14, 15,
+                      16, 17, 16, 17, 18, 19, 20, 21, 20, 21, 22, 23, 24, 25, 24, 25, 26, 27, 28,
+                      29, 28, 29, 30, 31, 32, 1];
+
+/// The permutation of the S-box outputs in the round function.
+static P: [u8; 32] = [16, 7, 20, 21, 29, 12, 28, 17, 1, 15, 23, 26, 5, 18, 31, 10, 2, 8, 24, 14,
+                      32, 27, 3, 9, 19, 13, 30, 6, 22, 11, 4, 25];
+
+/// Permuted choice 1, which selects the 56 key bits used by the key schedule.
+static PC1: [u8; 56] = [57, 49, 41, 33, 25, 17, 9, 1, 58, 50, 42, 34, 26, 18, 10, 2, 59, 51, 43,
+                        35, 27, 19, 11, 3, 60, 52, 44, 36, 63, 55, 47, 39, 31, 23, 15, 7, 62, 54,
+                        46, 38, 30, 22, 14, 6, 61, 53, 45, 37, 29, 21, 13, 5, 28, 20, 12, 4];
+
+/// Permuted choice 2, which selects the 48 bits of each round key.
+static PC2: [u8; 48] = [14, 17, 11, 24, 1, 5, 3, 28, 15, 6, 21, 10, 23, 19, 12, 4, 26, 8, 16, 7,
+                        27, 20, 13, 2, 41, 52, 31, 37, 47, 55, 30, 40, 51, 45, 33, 48, 44, 49,
+                        39, 56, 34, 53, 46, 42, 50, 36, 29, 32];
+
+/// The number of places to rotate each half of the key by before each round.
+static SHIFTS: [u32; 16] = [1, 1, 2, 2, 2, 2, 2, 2, 1, 2, 2, 2, 2, 2, 2, 1];
+
+/// The eight S-boxes, each indexed by a row (the outer bits of the input) and a column (the
+/// inner four bits).
+static SBOXES: [[[u8; 16]; 4]; 8] =
+    [[[14, 4, 13, 1, 2, 15, 11, 8, 3, 10, 6, 12, 5, 9, 0, 7],
+      [0, 15, 7, 4, 14, 2, 13, 1, 10, 6, 12, 11, 9, 5, 3, 8],
+      [4, 1, 14, 8, 13, 6, 2, 11, 15, 12, 9, 7, 3, 10, 5, 0],
+      [15, 12, 8, 2, 4, 9, 1, 7, 5, 11, 3, 14, 10, 0, 6, 13]],
+     [[15, 1, 8, 14, 6, 11, 3, 4, 9, 7, 2, 13, 12, 0, 5, 10],
+      [3, 13, 4, 7, 15, 2, 8, 14, 12, 0, 1, 10, 6, 9, 11, 5],
+      [0, 14, 7, 11, 10, 4, 13, 1, 5, 8, 12, 6, 9, 3, 2, 15],
+      [13, 8, 10, 1, 3, 15, 4, 2, 11, 6, 7, 12, 0, 5, 14, 9]],
+     [[10, 0, 9, 14, 6, 3, 15, 5, 1, 13, 12, 7, 11, 4, 2, 8],
+      [13, 7, 0, 9, 3, 4, 6, 10, 2, 8, 5, 14, 12, 11, 15, 1],
+      [13, 6, 4, 9, 8, 15, 3, 0, 11, 1, 2, 12, 5, 10, 14, 7],
+      [1, 10, 13, 0, 6, 9, 8, 7, 4, 15, 14, 3, 11, 5, 2, 12]],
+     [[7, 13, 14, 3, 0, 6, 9, 10, 1, 2, 8, 5, 11, 12, 4, 15],
+      [13, 8, 11, 5, 6, 15, 0, 3, 4, 7, 2, 12, 1, 10, 14, 9],
+      [10, 6, 9, 0, 12, 11, 7, 13, 15, 1, 3, 14, 5, 2, 8, 4],
+      [3, 15, 0, 6, 10, 1, 13, 8, 9, 4, 5, 11, 12, 7, 2, 14]],
+     [[2, 12, 4, 1, 7, 10, 11, 6, 8, 5, 3, 15, 13, 0, 14, 9],
+      [14, 11, 2, 12, 4, 7, 13, 1, 5, 0, 15, 10, 3, 9, 8, 6],
+      [4, 2, 1, 11, 10, 13, 7, 8, 15, 9, 12, 5, 6, 3, 0, 14],
+      [11, 8, 12, 7, 1, 14, 2, 13, 6, 15, 0, 9, 10, 4, 5, 3]],
+     [[12, 1, 10, 15, 9, 2, 6, 8, 0, 13, 3, 4, 14, 7, 5, 11],
+      [10, 15, 4, 2, 7, 12, 9, 5, 6, 1, 13, 14, 0, 11, 3, 8],
+      [9, 14, 15, 5, 2, 8, 12, 3, 7, 0, 4, 10, 1, 13, 11, 6],
+      [4, 3, 2, 12, 9, 5, 15, 10, 11, 14, 1, 7, 6, 0, 8, 13]],
+     [[4, 11, 2, 14, 15, 0, 8, 13, 3, 12, 9, 7, 5, 10, 6, 1],
+      [13, 0, 11, 7, 4, 9, 1, 10, 14, 3, 5, 12, 2, 15, 8, 6],
+      [1, 4, 11, 13, 12, 3, 7, 14, 10, 15, 6, 8, 0, 5, 9, 2],
+      [6, 11, 13, 8, 1, 4, 10, 7, 9, 5, 0, 15, 14, 2, 3, 12]],
+     [[13, 2, 8, 4, 6, 15, 11, 1, 10, 9, 3, 14, 5, 0, 12, 7],
+      [1, 15, 13, 8, 10, 3, 7, 4, 12, 5, 6, 11, 0, 14, 9, 2],
+      [7, 11, 4, 1, 9, 12, 14, 2, 0, 6, 10, 13, 15, 3, 5, 8],
+      [2, 1, 14, 7, 4, 10, 8, 13, 15, 12, 9, 0, 3, 5, 6, 11]]];
+
+/// Errors that can arise in preparing the key for the cipher.
+pub enum DesKeyError {
+    /// The key for DES was of an invalid length.
+    BadDesKeyLength,
+    /// The key for Triple-DES was of an invalid length.
+    BadTripleDesKeyLength,
+}
+
+impl fmt::Display for DesKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BadDesKeyLength => write!(f, "Invalid key length - must be 8 bytes"),
+            BadTripleDesKeyLength => write!(f, "Invalid key length - must be 16 or 24 bytes"),
+        }
+    }
+}
+
+impl fmt::Debug for DesKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self, f)
+    }
+}
+
+impl error::Error for DesKeyError {
+    fn description(&self) -> &str {
+        match *self {
+            BadDesKeyLength => "invalid key length",
+            BadTripleDesKeyLength => "invalid key length",
+        }
+    }
+}
+
+/// Applies a permutation table to the lowest `input_bits` bits of the input.
+fn permute(input: u64, input_bits: u32, table: &[u8]) -> u64 {
+    table.iter().fold(0, |output, &position| {
+        (output << 1) | ((input >> (input_bits - position as u32)) & 1)
+    })
+}
+
+/// The DES round function, applied to a 32-bit half-block with a 48-bit round key.
+fn feistel(half: u32, round_key: u64) -> u32 {
+    let expanded = permute(half as u64, 32, &E) ^ round_key;
+    let mut substituted = 0;
+    for (ix, sbox) in SBOXES.iter().enumerate() {
+        let chunk = (expanded >> (42 - 6 * ix)) as usize & 0x3f;
+        let row = (chunk >> 4 & 2) | (chunk & 1);
+        let column = (chunk >> 1) & 0xf;
+        substituted = (substituted << 4) | sbox[row][column] as u64;
+    }
+    permute(substituted, 32, &P) as u32
+}
+
+/// A DES encryptor and decryptor.
+pub struct DesCipher {
+    /// The 48-bit round keys, in the order they are used for encryption.
+    round_keys: [u64; 16],
+}
+
+impl DesCipher {
+    /// Creates a new DesCipher which uses the given 8-byte key for encryption and decryption of
+    /// blocks of data. The parity bits of the key are ignored.
+    pub fn new(key: &[u8]) -> Result<DesCipher, DesKeyError> {
+        if key.len() != 8 {
+            return Err(BadDesKeyLength);
+        }
+
+        // Select the 56 key bits, and split them into two 28-bit halves.
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(key);
+        let selected = permute(u64::from_be_bytes(bytes), 64, &PC1);
+        let mut left = (selected >> 28) as u32;
+        let mut right = selected as u32 & 0x0fff_ffff;
+
+        // Rotate the halves before each round, and select the round key from them.
+        let mut round_keys = [0; 16];
+        for (round_key, &shift) in round_keys.iter_mut().zip(SHIFTS.iter()) {
+            left = ((left << shift) | (left >> (28 - shift))) & 0x0fff_ffff;
+            right = ((right << shift) | (right >> (28 - shift))) & 0x0fff_ffff;
+            *round_key = permute((left as u64) << 28 | right as u64, 56, &PC2);
+        }
+
+        Ok(DesCipher { round_keys })
+    }
+
+    /// Runs the sixteen Feistel rounds over a block, using the given round keys in order.
+    fn rounds<'a, I: Iterator<Item = &'a u64>>(block: &mut [u8], round_keys: I) {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(block);
+        let permuted = permute(u64::from_be_bytes(bytes), 64, &IP);
+        let (mut left, mut right) = ((permuted >> 32) as u32, permuted as u32);
+        for &round_key in round_keys {
+            let next = left ^ feistel(right, round_key);
+            left = right;
+            right = next;
+        }
+
+        // The halves are swapped back after the last round.
+        let output = permute((right as u64) << 32 | left as u64, 64, &FP);
+        block.copy_from_slice(&output.to_be_bytes());
+    }
+}
+
+impl Cipher for DesCipher {
+    /// Encrypt a single block of 8 bytes in place using the DES algorithm.
+    fn encrypt_block(&self, block: &mut [u8]) {
+        DesCipher::rounds(block, self.round_keys.iter());
+    }
+
+    /// Decrypt a single block of 8 bytes in place using the DES algorithm.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        DesCipher::rounds(block, self.round_keys.iter().rev());
+    }
+
+    /// The size of a block for the DES cipher.
+    fn block_size(&self) -> usize {
+        8
+    }
+}
+
+/// A Triple-DES encryptor and decryptor, using encrypt-decrypt-encrypt (EDE) with either two or
+/// three independent keys.
+pub struct TripleDesCipher {
+    /// The ciphers for the first, second and third keys.
+    ciphers: [DesCipher; 3],
+}
+
+impl TripleDesCipher {
+    /// Creates a new TripleDesCipher which uses the given key for encryption and decryption of
+    /// blocks of data. A 24-byte key is split into three independent keys (EDE3), while a
+    /// 16-byte key is split into two, with the first also used as the third (EDE2).
+    pub fn new(key: &[u8]) -> Result<TripleDesCipher, DesKeyError> {
+        let third = match key.len() {
+            16 => &key[..8],
+            24 => &key[16..],
+            _ => return Err(BadTripleDesKeyLength),
+        };
+
+        Ok(TripleDesCipher {
+            ciphers: [DesCipher::new(&key[..8])?,
+                      DesCipher::new(&key[8..16])?,
+                      DesCipher::new(third)?],
+        })
+    }
+}
+
+impl Cipher for TripleDesCipher {
+    /// Encrypt a single block of 8 bytes in place using the Triple-DES algorithm.
+    fn encrypt_block(&self, block: &mut [u8]) {
+        self.ciphers[0].encrypt_block(block);
+        self.ciphers[1].decrypt_block(block);
+        self.ciphers[2].encrypt_block(block);
+    }
+
+    /// Decrypt a single block of 8 bytes in place using the Triple-DES algorithm.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        self.ciphers[2].decrypt_block(block);
+        self.ciphers[1].encrypt_block(block);
+        self.ciphers[0].decrypt_block(block);
+    }
+
+    /// The size of a block for the Triple-DES cipher.
+    fn block_size(&self) -> usize {
+        8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes};
+    use utils::data::Data;
+
+    const PLAINTEXT: &str = "Now is the time for all ";
+    const IV: &str = "1234567890abcdef";
+
+    fn check_vector(algorithm: Algorithms, key: &str, mode: OperationModes, ciphertext: &str) {
+        let key = Data::from_hex(key).unwrap();
+        let block = BlockCipher::new(algorithm, mode, PaddingSchemes::NoPadding, &key).unwrap();
+        let encrypted = block.encrypt(&Data::from_text(PLAINTEXT)).unwrap();
+        assert_eq!(encrypted.to_hex(), ciphertext);
+        assert_eq!(block.decrypt(&encrypted).unwrap().to_text(), PLAINTEXT);
+    }
+
+    #[test]
+    fn des_single_block() {
+        let key = Data::from_hex("133457799bbcdff1").unwrap();
+        let block = BlockCipher::new(Algorithms::Des,
+                                     OperationModes::Ecb,
+                                     PaddingSchemes::NoPadding,
+                                     &key)
+            .unwrap();
+        let ciphertext = block.encrypt(&Data::from_hex("0123456789abcdef").unwrap()).unwrap();
+        assert_eq!(ciphertext.to_hex(), "85e813540f0ab405");
+    }
+
+    #[test]
+    fn des_fips_81() {
+        check_vector(Algorithms::Des,
+                     "0123456789abcdef",
+                     OperationModes::Ecb,
+                     "3fa40e8a984d48156a271787ab8883f9893d51ec4b563b53");
+        check_vector(Algorithms::Des,
+                     "0123456789abcdef",
+                     OperationModes::Cbc(Data::from_hex(IV).unwrap()),
+                     "e5c7cdde872bf27c43e934008c389c0f683788499a7c05f6");
+    }
+
+    #[test]
+    fn triple_des() {
+        let ede3 = "0123456789abcdef23456789abcdef01456789abcdef0123";
+        let ede2 = "0123456789abcdef23456789abcdef01";
+        check_vector(Algorithms::TripleDes,
+                     ede3,
+                     OperationModes::Ecb,
+                     "314f8327fa7a09a84362760cc13ba7daff55c5f80faaac45");
+        check_vector(Algorithms::TripleDes,
+                     ede3,
+                     OperationModes::Cbc(Data::from_hex(IV).unwrap()),
+                     "f3c0ff026c023089656fbb169def7edb30ba36075d6f0176");
+        check_vector(Algorithms::TripleDes,
+                     ede2,
+                     OperationModes::Ecb,
+                     "b7835779ee26acb75d2731a8d9b401623dd3fc69a08cc6d9");
+        check_vector(Algorithms::TripleDes,
+                     ede2,
+                     OperationModes::Cbc(Data::from_hex(IV).unwrap()),
+                     "134b98f8eeb3f6079f1a82e0640d5f2f8e090661c42864a1");
+
+        // With all three keys equal, Triple-DES is the same as single DES.
+        check_vector(Algorithms::TripleDes,
+                     "0123456789abcdef0123456789abcdef0123456789abcdef",
+                     OperationModes::Ecb,
+                     "3fa40e8a984d48156a271787ab8883f9893d51ec4b563b53");
+    }
+
+    #[test]
+    fn key_lengths() {
+        let des = BlockCipher::new(Algorithms::Des,
+                                   OperationModes::Ecb,
+                                   PaddingSchemes::Pkcs7,
+                                   &Data::from_bytes(vec![0; 16]));
+        assert_eq!(des.err().unwrap(), "Invalid key length - must be 8 bytes");
+        let triple_des = BlockCipher::new(Algorithms::TripleDes,
+                                          OperationModes::Ecb,
+                                          PaddingSchemes::Pkcs7,
+                                          &Data::from_bytes(vec![0; 8]));
+        assert_eq!(triple_des.err().unwrap(), "Invalid key length - must be 16 or 24 bytes");
+    }
+}
@@ -0,0 +1,138 @@
+//! A framework for building block ciphers from balanced Feistel networks.
+//!
+//! A Feistel network splits each block into a left and a right half, and in each round XORs the
+//! output of a keyed round function of the left half into the right half before swapping the
+//! halves. The final swap is undone, so that decryption is the same network with the rounds in
+//! reverse order, whatever the round function. Optionally, fixed whitening keys can be XORed
+//! into the block before the first round and after the last.
+//!
+//! A cipher is defined by implementing `FeistelFunction`, whose constructor plays the part of the
+//! key schedule, producing the round keys used by the round function. Wrapping it in a
+//! `FeistelCipher` gives a `Cipher` which can be used with `BlockCipher::from_cipher`.
+
+use utils::block::{Cipher, xor_in_place};
+
+/// The keyed round function of a balanced Feistel network.
+pub trait FeistelFunction: Send + Sync {
+    /// The size of each half of the block, in bytes.
+    fn half_size(&self) -> usize;
+
+    /// The number of rounds in the network.
+    fn rounds(&self) -> usize;
+
+    /// Computes the round function for the given round, counting from zero, on the input half,
+    /// and XORs the result into the output half.
+    fn apply(&self, round: usize, input: &[u8], output: &mut [u8]);
+
+    /// A whitening key to XOR into the whole block before the first round of encryption, if any.
+    fn input_whitening(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// A whitening key to XOR into the whole block after the last round of encryption, if any.
+    fn output_whitening(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// A block cipher built from a Feistel network with the given round function.
+pub struct FeistelCipher<F: FeistelFunction> {
+    /// The round function, including its round keys.
+    function: F,
+}
+
+impl<F: FeistelFunction> FeistelCipher<F> {
+    /// Returns a new FeistelCipher which uses the given round function.
+    pub fn new(function: F) -> FeistelCipher<F> {
+        FeistelCipher { function }
+    }
+
+    /// Runs the rounds of the network over a block in the given order, undoing the final swap.
+    fn rounds<I: Iterator<Item = usize>>(&self, block: &mut [u8], rounds: I) {
+        let half_size = self.function.half_size();
+        for round in rounds {
+            {
+                let (left, right) = block.split_at_mut(half_size);
+                self.function.apply(round, left, right);
+            }
+            block.rotate_left(half_size);
+        }
+        block.rotate_left(half_size);
+    }
+}
+
+impl<F: FeistelFunction> Cipher for FeistelCipher<F> {
+    /// Encrypt a single block in place by running the network forwards.
+    fn encrypt_block(&self, block: &mut [u8]) {
+        if let Some(key) = self.function.input_whitening() {
+            xor_in_place(block, key);
+        }
+        self.rounds(block, 0..self.function.rounds());
+        if let Some(key) = self.function.output_whitening() {
+            xor_in_place(block, key);
+        }
+    }
+
+    /// Decrypt a single block in place by running the network backwards.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        if let Some(key) = self.function.output_whitening() {
+            xor_in_place(block, key);
+        }
+        self.rounds(block, (0..self.function.rounds()).rev());
+        if let Some(key) = self.function.input_whitening() {
+            xor_in_place(block, key);
+        }
+    }
+
+    /// The size of a block, which is two halves.
+    fn block_size(&self) -> usize {
+        2 * self.function.half_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::{BlockCipher, OperationModes, PaddingSchemes};
+    use utils::block::feistel::{FeistelCipher, FeistelFunction};
+    use utils::data::Data;
+
+    /// A toy Feistel function on 3-byte halves, which is deliberately not invertible.
+    struct ToyFunction {
+        round_keys: Vec<u8>,
+    }
+
+    impl FeistelFunction for ToyFunction {
+        fn half_size(&self) -> usize {
+            3
+        }
+
+        fn rounds(&self) -> usize {
+            self.round_keys.len()
+        }
+
+        fn apply(&self, round: usize, input: &[u8], output: &mut [u8]) {
+            let sum = input.iter().fold(self.round_keys[round], |acc, &x| acc.wrapping_add(x));
+            for (ix, byte) in output.iter_mut().enumerate() {
+                *byte ^= sum.wrapping_mul(ix as u8 + 1) & input[ix];
+            }
+        }
+
+        fn input_whitening(&self) -> Option<&[u8]> {
+            Some(&[1, 2, 3, 4, 5, 6])
+        }
+    }
+
+    #[test]
+    fn decryption_inverts_encryption() {
+        let function = ToyFunction { round_keys: vec![0x13, 0x57, 0x9b, 0xdf, 0x24] };
+        let block = BlockCipher::from_cipher(Box::new(FeistelCipher::new(function)),
+                                             OperationModes::Cbc(Data::from_bytes(vec![0; 6])),
+                                             PaddingSchemes::Pkcs7);
+        let plaintext = Data::from_text("The quick brown fox jumps over the lazy dog");
+        let ciphertext = block.encrypt(&plaintext).unwrap();
+        assert_eq!(ciphertext.len(), 48);
+        assert!(ciphertext.bytes() != plaintext.bytes());
+        assert_eq!(block.decrypt(&ciphertext).unwrap().to_text(), plaintext.to_text());
+    }
+}
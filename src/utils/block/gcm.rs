@@ -0,0 +1,299 @@
+//! Authenticated encryption and decryption using AES in Galois/Counter Mode (GCM).
+//!
+//! See NIST SP 800-38D.
+
+use utils::block::{Cipher, EncryptError, DecryptError, xor_in_place};
+use utils::block::aes::AesCipher;
+use utils::data::Data;
+use utils::gf128::Gf128;
+use utils::mac::fixed_time_eq;
+
+/// An AES-GCM encryptor and decryptor.
+pub struct AesGcm {
+    /// The AES cipher used to produce the keystream and mask the tag.
+    cipher: AesCipher,
+    /// The hash subkey H, which is the encryption of the zero block.
+    hash_key: Gf128,
+    /// The length, in bytes, of the authentication tags produced and checked.
+    tag_len: usize,
+}
+
+impl AesGcm {
+    /// Creates a new AesGcm which uses the given key, and produces tags of the given length in
+    /// bytes. The allowed tag lengths are 4, 8 and 12 to 16 bytes.
+    pub fn new(key: &Data, tag_len: usize) -> Result<AesGcm, String> {
+
+        // Check that the tag length is supported.
+        match tag_len {
+            4 | 8 | 12..=16 => {}
+            _ => {
+                return Err(format!("Invalid tag length {} - must be 4, 8 or 12-16 bytes", tag_len))
+            }
+        }
+
+        match AesCipher::new(key.bytes()) {
+            Ok(aes) => {
+                let mut zero_block = [0; 16];
+                aes.encrypt_block(&mut zero_block);
+                let hash_key = Gf128::from_bytes(&zero_block);
+                Ok(AesGcm {
+                    cipher: aes,
+                    hash_key,
+                    tag_len,
+                })
+            }
+            Err(err) => Err(format!("{}", err)),
+        }
+    }
+
+    /// Encrypts the given plaintext and authenticates it along with the associated data, using
+    /// the given initialisation vector. Returns the ciphertext and the authentication tag.
+    pub fn encrypt(&self,
+                   iv: &Data,
+                   aad: &Data,
+                   plaintext: &Data)
+                   -> Result<(Data, Data), EncryptError> {
+
+        // Check that the initialisation vector is not empty.
+        if iv.is_empty() {
+            return Err(EncryptError::IVLength);
+        }
+
+        // Encrypt the plaintext, starting from the block after the pre-counter block, and then
+        // compute the tag over the ciphertext.
+        let pre_counter = self.pre_counter_block(iv);
+        let ciphertext = self.gctr(&inc32(&pre_counter), plaintext);
+        let tag = self.tag(&pre_counter, aad, &ciphertext);
+
+        Ok((ciphertext, tag))
+    }
+
+    /// Checks the authentication tag of the given ciphertext and associated data, and decrypts
+    /// the ciphertext if it is valid.
+    pub fn decrypt(&self,
+                   iv: &Data,
+                   aad: &Data,
+                   ciphertext: &Data,
+                   tag: &Data)
+                   -> Result<Data, DecryptError> {
+
+        // Check that the initialisation vector is not empty.
+        if iv.is_empty() {
+            return Err(DecryptError::IVLength);
+        }
+
+        // Recompute the tag and check it against the given one before decrypting anything.
+        let pre_counter = self.pre_counter_block(iv);
+        let expected_tag = self.tag(&pre_counter, aad, ciphertext);
+        if !fixed_time_eq(expected_tag.bytes(), tag.bytes()) {
+            return Err(DecryptError::Authentication);
+        }
+
+        Ok(self.gctr(&inc32(&pre_counter), ciphertext))
+    }
+
+    /// Returns the pre-counter block J0 derived from the given initialisation vector.
+    fn pre_counter_block(&self, iv: &Data) -> [u8; 16] {
+        if iv.len() == 12 {
+            // The common case: the IV followed by a 32-bit counter of 1.
+            let mut block = [0; 16];
+            block[..12].copy_from_slice(iv.bytes());
+            block[15] = 1;
+            block
+        } else {
+            // Otherwise, hash the IV.
+            ghash(&self.hash_key, &Data::new(), iv).to_bytes()
+        }
+    }
+
+    /// Computes the authentication tag for the given associated data and ciphertext.
+    fn tag(&self, pre_counter: &[u8; 16], aad: &Data, ciphertext: &Data) -> Data {
+        let hash = ghash(&self.hash_key, aad, ciphertext);
+        let full_tag = self.gctr(pre_counter, &hash.to_data());
+        full_tag.slice(0, self.tag_len)
+    }
+
+    /// Applies the GCTR function to the given data, starting from the given counter block and
+    /// incrementing the rightmost 32 bits for each subsequent block.
+    fn gctr(&self, initial_counter: &[u8; 16], data: &Data) -> Data {
+
+        // Copy the message into the output, and XOR it in place, one block at a time, with the
+        // encrypted counter block. The final block may be only partially used.
+        let mut output = data.bytes().to_vec();
+        let mut counter = *initial_counter;
+        for block in output.chunks_mut(16) {
+            let mut keystream = counter;
+            self.cipher.encrypt_block(&mut keystream);
+            xor_in_place(block, &keystream);
+            counter = inc32(&counter);
+        }
+
+        Data::from_bytes(output)
+    }
+}
+
+/// Computes GHASH, keyed by the hash subkey H, over the given associated data and ciphertext.
+///
+/// Both inputs are padded with zeroes to a whole number of blocks, and followed by a block
+/// containing their lengths in bits, as in the computation of a GCM authentication tag.
+pub fn ghash(hash_key: &Gf128, aad: &Data, ciphertext: &Data) -> Gf128 {
+
+    // Absorb each block of the associated data and then the ciphertext.
+    let mut hash = Gf128::zero();
+    for in_block in aad.bytes().chunks(16).chain(ciphertext.bytes().chunks(16)) {
+        hash = (hash + Gf128::from_bytes(in_block)) * *hash_key;
+    }
+
+    // Finally absorb the lengths of the inputs.
+    let mut lengths = [0; 16];
+    lengths[..8].copy_from_slice(&(8 * aad.len() as u64).to_be_bytes());
+    lengths[8..].copy_from_slice(&(8 * ciphertext.len() as u64).to_be_bytes());
+    (hash + Gf128::from_bytes(&lengths)) * *hash_key
+}
+
+/// Increments the rightmost 32 bits of the given counter block, modulo 2^32.
+fn inc32(block: &[u8; 16]) -> [u8; 16] {
+    let mut output = *block;
+    let counter = u32::from_be_bytes([block[12], block[13], block[14], block[15]]);
+    output[12..].copy_from_slice(&counter.wrapping_add(1).to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::gcm::AesGcm;
+    use utils::data::Data;
+
+    /// The key used by test cases 3 to 6 of the GCM specification.
+    const KEY: &str = "feffe9928665731c6d6a8f9467308308";
+
+    /// The plaintext used by test cases 3 to 6 of the GCM specification.
+    const PLAINTEXT: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255";
+
+    /// The associated data used by test cases 4 to 6 of the GCM specification.
+    const AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+
+    /// Encrypt and decrypt the given plaintext, and check the ciphertext and tag.
+    fn check_vector(key: &str, iv: &str, plaintext: &str, aad: &str, ciphertext: &str, tag: &str) {
+        let gcm = AesGcm::new(&Data::from_hex(key).unwrap(), 16).unwrap();
+        let iv = Data::from_hex(iv).unwrap();
+        let aad = Data::from_hex(aad).unwrap();
+        let (actual_ciphertext, actual_tag) =
+            gcm.encrypt(&iv, &aad, &Data::from_hex(plaintext).unwrap()).unwrap();
+        assert_eq!(actual_ciphertext.to_hex(), ciphertext);
+        assert_eq!(actual_tag.to_hex(), tag);
+        let decrypted = gcm.decrypt(&iv, &aad, &actual_ciphertext, &actual_tag).unwrap();
+        assert_eq!(decrypted.to_hex(), plaintext);
+    }
+
+    #[test]
+    fn test_case_1() {
+        check_vector("00000000000000000000000000000000",
+                     "000000000000000000000000",
+                     "",
+                     "",
+                     "",
+                     "58e2fccefa7e3061367f1d57a4e7455a");
+    }
+
+    #[test]
+    fn test_case_2() {
+        check_vector("00000000000000000000000000000000",
+                     "000000000000000000000000",
+                     "00000000000000000000000000000000",
+                     "",
+                     "0388dace60b6a392f328c2b971b2fe78",
+                     "ab6e47d42cec13bdf53a67b21257bddf");
+    }
+
+    #[test]
+    fn test_case_3() {
+        check_vector(KEY,
+                     "cafebabefacedbaddecaf888",
+                     PLAINTEXT,
+                     "",
+                     "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                      21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985",
+                     "4d5c2af327cd64a62cf35abd2ba6fab4");
+    }
+
+    #[test]
+    fn test_case_4() {
+        check_vector(KEY,
+                     "cafebabefacedbaddecaf888",
+                     &PLAINTEXT[..120],
+                     AAD,
+                     "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                      21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+                     "5bc94fbc3221a5db94fae95ae7121a47");
+    }
+
+    #[test]
+    fn test_case_5() {
+        check_vector(KEY,
+                     "cafebabefacedbad",
+                     &PLAINTEXT[..120],
+                     AAD,
+                     "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+                      73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+                     "3612d2e79e3b0785561be14aaca2fccb");
+    }
+
+    #[test]
+    fn test_case_6() {
+        check_vector(KEY,
+                     "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+                      c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+                     &PLAINTEXT[..120],
+                     AAD,
+                     "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+                      01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+                     "619cc5aefffe0bfa462af43c1699d050");
+    }
+
+    #[test]
+    fn test_case_16() {
+        check_vector("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+                     "cafebabefacedbaddecaf888",
+                     &PLAINTEXT[..120],
+                     AAD,
+                     "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+                      8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+                     "76fc6ece0f4e1768cddf8853bb2d551b");
+    }
+
+    #[test]
+    fn truncated_tags() {
+        let gcm = AesGcm::new(&Data::from_hex(KEY).unwrap(), 4).unwrap();
+        let iv = Data::from_hex("cafebabefacedbaddecaf888").unwrap();
+        let aad = Data::from_hex(AAD).unwrap();
+        let plaintext = Data::from_hex(&PLAINTEXT[..120]).unwrap();
+        let (ciphertext, tag) = gcm.encrypt(&iv, &aad, &plaintext).unwrap();
+        assert_eq!(tag.to_hex(), "5bc94fbc");
+        assert!(AesGcm::new(&Data::from_hex(KEY).unwrap(), 3).is_err());
+        assert!(AesGcm::new(&Data::from_hex(KEY).unwrap(), 17).is_err());
+        assert!(gcm.decrypt(&iv, &aad, &ciphertext, &tag).is_ok());
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let gcm = AesGcm::new(&Data::from_hex(KEY).unwrap(), 16).unwrap();
+        let iv = Data::from_hex("cafebabefacedbaddecaf888").unwrap();
+        let aad = Data::from_hex(AAD).unwrap();
+        let plaintext = Data::from_hex(&PLAINTEXT[..120]).unwrap();
+        let (ciphertext, tag) = gcm.encrypt(&iv, &aad, &plaintext).unwrap();
+
+        // Flip a bit of the ciphertext.
+        let mut bytes = ciphertext.bytes().to_vec();
+        bytes[0] ^= 1;
+        assert!(gcm.decrypt(&iv, &aad, &Data::from_bytes(bytes), &tag).is_err());
+
+        // Change the associated data.
+        assert!(gcm.decrypt(&iv, &Data::new(), &ciphertext, &tag).is_err());
+
+        // Truncate the tag.
+        assert!(gcm.decrypt(&iv, &aad, &ciphertext, &tag.slice(0, 15)).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Wrapping and unwrapping of key material using the AES Key Wrap algorithms.
+//!
+//! See RFC 3394 for AES Key Wrap, and RFC 5649 for AES Key Wrap with Padding.
+
+use utils::block::{Cipher, EncryptError, DecryptError};
+use utils::block::aes::AesCipher;
+use utils::data::Data;
+use utils::mac::fixed_time_eq;
+
+/// The default initial value from RFC 3394, which is checked on unwrapping.
+const DEFAULT_IV: [u8; 8] = [0xa6; 8];
+
+/// The constant first half of the alternative initial value from RFC 5649. The second half is
+/// the length of the unpadded key data.
+const PADDING_IV_PREFIX: [u8; 4] = [0xa6, 0x59, 0x59, 0xa6];
+
+/// A wrapper and unwrapper of keys using AES Key Wrap, with or without padding.
+///
+/// Wrapping encrypts key material under a key-encryption key and adds an 8-byte integrity check
+/// value, so that any change to the wrapped key is detected when it is unwrapped.
+pub struct AesKeyWrap {
+    /// The AES cipher keyed with the key-encryption key.
+    cipher: AesCipher,
+}
+
+impl AesKeyWrap {
+    /// Creates a new AesKeyWrap which uses the given key-encryption key, which must be a valid AES
+    /// key.
+    pub fn new(key: &Data) -> Result<AesKeyWrap, String> {
+        match AesCipher::new(key.bytes()) {
+            Ok(cipher) => Ok(AesKeyWrap { cipher }),
+            Err(err) => Err(format!("{}", err)),
+        }
+    }
+
+    /// Wraps the given key data using RFC 3394. The key data must be a whole number of 8-byte
+    /// blocks, and at least two blocks long.
+    pub fn wrap(&self, key_data: &Data) -> Result<Data, EncryptError> {
+        if key_data.len() < 16 || !key_data.len().is_multiple_of(8) {
+            return Err(EncryptError::DataLength);
+        }
+        Ok(Data::from_bytes(self.wrap_blocks(DEFAULT_IV, key_data.bytes())))
+    }
+
+    /// Unwraps the given wrapped key using RFC 3394, checking that it has not been modified.
+    pub fn unwrap(&self, wrapped: &Data) -> Result<Data, DecryptError> {
+        if wrapped.len() < 24 || !wrapped.len().is_multiple_of(8) {
+            return Err(DecryptError::DataLength);
+        }
+        let (iv, key_data) = self.unwrap_blocks(wrapped.bytes());
+        if !fixed_time_eq(&iv, &DEFAULT_IV) {
+            return Err(DecryptError::Integrity);
+        }
+        Ok(Data::from_bytes(key_data))
+    }
+
+    /// Wraps the given key data using RFC 5649, which allows key data of any non-zero length.
+    pub fn wrap_with_padding(&self, key_data: &Data) -> Result<Data, EncryptError> {
+        if key_data.is_empty() || key_data.len() > u32::MAX as usize {
+            return Err(EncryptError::DataLength);
+        }
+
+        // The initial value records the length of the key data, which is then padded with zeroes
+        // to a whole number of blocks.
+        let mut iv = [0; 8];
+        iv[..4].copy_from_slice(&PADDING_IV_PREFIX);
+        iv[4..].copy_from_slice(&(key_data.len() as u32).to_be_bytes());
+        let mut padded = key_data.bytes().to_vec();
+        padded.resize(key_data.len().div_ceil(8) * 8, 0);
+
+        // A single block is encrypted directly along with the initial value, rather than wrapped.
+        if padded.len() == 8 {
+            let mut block = iv.to_vec();
+            block.extend_from_slice(&padded);
+            self.cipher.encrypt_block(&mut block);
+            return Ok(Data::from_bytes(block));
+        }
+        Ok(Data::from_bytes(self.wrap_blocks(iv, &padded)))
+    }
+
+    /// Unwraps the given wrapped key using RFC 5649, checking that it has not been modified and
+    /// removing the padding.
+    pub fn unwrap_with_padding(&self, wrapped: &Data) -> Result<Data, DecryptError> {
+        if wrapped.len() < 16 || !wrapped.len().is_multiple_of(8) {
+            return Err(DecryptError::DataLength);
+        }
+
+        let (iv, mut padded) = if wrapped.len() == 16 {
+            let mut block = wrapped.bytes().to_vec();
+            self.cipher.decrypt_block(&mut block);
+            let key_data = block.split_off(8);
+            let mut iv = [0; 8];
+            iv.copy_from_slice(&block);
+            (iv, key_data)
+        } else {
+            self.unwrap_blocks(wrapped.bytes())
+        };
+
+        // Check the constant part of the initial value, that the length fits in the padded data
+        // with fewer than eight bytes to spare, and that the padding is all zeroes.
+        let mut length_bytes = [0; 4];
+        length_bytes.copy_from_slice(&iv[4..]);
+        let length = u32::from_be_bytes(length_bytes) as usize;
+        if !fixed_time_eq(&iv[..4], &PADDING_IV_PREFIX) || length > padded.len() ||
+           length + 8 <= padded.len() ||
+           padded[length..].iter().any(|&byte| byte != 0) {
+            return Err(DecryptError::Integrity);
+        }
+        padded.truncate(length);
+        Ok(Data::from_bytes(padded))
+    }
+
+    /// Applies the wrapping function W to the given blocks of key data, starting from the given
+    /// initial value. Returns the final integrity register followed by the wrapped blocks.
+    fn wrap_blocks(&self, iv: [u8; 8], key_data: &[u8]) -> Vec<u8> {
+        let n = key_data.len() / 8;
+        let mut register = iv;
+        let mut output = vec![0; 8];
+        output.extend_from_slice(key_data);
+
+        // Each of the six passes encrypts every block along with the register, and mixes a step
+        // counter into the register so that the blocks cannot be reordered.
+        let mut block = [0; 16];
+        for j in 0..6 {
+            for i in 1..=n {
+                block[..8].copy_from_slice(&register);
+                block[8..].copy_from_slice(&output[8 * i..8 * i + 8]);
+                self.cipher.encrypt_block(&mut block);
+                let step = (n * j + i) as u64;
+                register = (u64::from_be_bytes(first_half(&block)) ^ step).to_be_bytes();
+                output[8 * i..8 * i + 8].copy_from_slice(&block[8..]);
+            }
+        }
+
+        output[..8].copy_from_slice(&register);
+        output
+    }
+
+    /// Applies the unwrapping function W^-1 to the given wrapped blocks. Returns the recovered
+    /// initial value and the key data, which must be checked by the caller.
+    fn unwrap_blocks(&self, wrapped: &[u8]) -> ([u8; 8], Vec<u8>) {
+        let n = wrapped.len() / 8 - 1;
+        let mut register = first_half(wrapped);
+        let mut output = wrapped[8..].to_vec();
+
+        // Undo the passes of the wrapping function in reverse order.
+        let mut block = [0; 16];
+        for j in (0..6).rev() {
+            for i in (1..=n).rev() {
+                let step = (n * j + i) as u64;
+                block[..8].copy_from_slice(&(u64::from_be_bytes(register) ^ step).to_be_bytes());
+                block[8..].copy_from_slice(&output[8 * (i - 1)..8 * i]);
+                self.cipher.decrypt_block(&mut block);
+                register = first_half(&block);
+                output[8 * (i - 1)..8 * i].copy_from_slice(&block[8..]);
+            }
+        }
+
+        (register, output)
+    }
+}
+
+/// Returns the first eight bytes of the given slice.
+fn first_half(block: &[u8]) -> [u8; 8] {
+    let mut half = [0; 8];
+    half.copy_from_slice(&block[..8]);
+    half
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::DecryptError;
+    use utils::block::key_wrap::AesKeyWrap;
+    use utils::data::Data;
+
+    const KEK: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const KEY_DATA: &str = "00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f";
+
+    #[test]
+    fn rfc_3394_vectors() {
+        let vectors = [(16, 16, "1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5"),
+                       (24, 16, "96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d"),
+                       (32, 16, "64e8c3f9ce0f5ba263e9777905818a2a93c8191e7d6e8ae7"),
+                       (24,
+                        24,
+                        "031d33264e15d33268f24ec260743edce1c6c7ddee725a936ba814915c6762d2"),
+                       (32,
+                        24,
+                        "a8f9bc1612c68b3ff6e6f4fbe30e71e4769c8b80a32cb8958cd5d17d6b254da1"),
+                       (32,
+                        32,
+                        "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326\
+                         cbc7f0e71a99f43bfb988b9b7a02dd21")];
+        for &(kek_len, data_len, wrapped) in &vectors {
+            let kek = Data::from_hex(&KEK[..2 * kek_len]).unwrap();
+            let key_data = Data::from_hex(&KEY_DATA[..2 * data_len]).unwrap();
+            let key_wrap = AesKeyWrap::new(&kek).unwrap();
+            assert_eq!(key_wrap.wrap(&key_data).unwrap().to_hex(), wrapped);
+            let unwrapped = key_wrap.unwrap(&Data::from_hex(wrapped).unwrap()).unwrap();
+            assert_eq!(unwrapped.to_hex(), key_data.to_hex());
+        }
+    }
+
+    #[test]
+    fn rfc_5649_vectors() {
+        let kek = Data::from_hex("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8").unwrap();
+        let key_wrap = AesKeyWrap::new(&kek).unwrap();
+        let vectors = [("c37b7e6492584340bed12207808941155068f738",
+                        "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a"),
+                       ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f")];
+        for &(key_data, wrapped) in &vectors {
+            let key_data = Data::from_hex(key_data).unwrap();
+            assert_eq!(key_wrap.wrap_with_padding(&key_data).unwrap().to_hex(), wrapped);
+            let unwrapped = key_wrap.unwrap_with_padding(&Data::from_hex(wrapped).unwrap())
+                .unwrap();
+            assert_eq!(unwrapped.to_hex(), key_data.to_hex());
+        }
+    }
+
+    /// Returns a copy of the given data with a bit of the given byte flipped.
+    fn flip_bit(data: &Data, ix: usize) -> Data {
+        let mut bytes = data.bytes().to_vec();
+        bytes[ix] ^= 0x10;
+        Data::from_bytes(bytes)
+    }
+
+    #[test]
+    fn integrity_check() {
+        let key_wrap = AesKeyWrap::new(&Data::random(16)).unwrap();
+        let key_data = Data::random(32);
+
+        // Flipping any bit of a wrapped key is detected, with or without padding.
+        let wrapped = key_wrap.wrap(&key_data).unwrap();
+        let padded = key_wrap.wrap_with_padding(&key_data.slice(0, 5)).unwrap();
+        for ix in 0..wrapped.len() {
+            match key_wrap.unwrap(&flip_bit(&wrapped, ix)) {
+                Err(DecryptError::Integrity) => {}
+                _ => panic!("Tampering with byte {} was not detected", ix),
+            }
+        }
+        for ix in 0..padded.len() {
+            match key_wrap.unwrap_with_padding(&flip_bit(&padded, ix)) {
+                Err(DecryptError::Integrity) => {}
+                _ => panic!("Tampering with byte {} was not detected", ix),
+            }
+        }
+
+        // The two variants use different initial values, so they cannot be mixed up.
+        let wrapped = key_wrap.wrap(&key_data).unwrap();
+        assert!(key_wrap.unwrap_with_padding(&wrapped).is_err());
+        let wrapped = key_wrap.wrap_with_padding(&key_data).unwrap();
+        assert!(key_wrap.unwrap(&wrapped).is_err());
+
+        // Invalid lengths are rejected before doing any work.
+        assert!(key_wrap.wrap(&Data::random(8)).is_err());
+        assert!(key_wrap.wrap(&Data::random(20)).is_err());
+        assert!(key_wrap.wrap_with_padding(&Data::new()).is_err());
+        match key_wrap.unwrap(&Data::random(20)) {
+            Err(DecryptError::DataLength) => {}
+            _ => panic!("Expected a data length error"),
+        }
+    }
+}
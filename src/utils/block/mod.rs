@@ -1,40 +1,186 @@
 //! Block cipher encryption and decryption using various modes of operation.
 
 mod aes;
+mod aes_bitsliced;
+mod aesni;
+mod blowfish;
+mod des;
 mod null;
 
+pub mod feistel;
+pub mod gcm;
+pub mod key_wrap;
+pub mod streaming;
+pub mod xts;
+
 use std::fmt;
 use std::error;
+use std::thread;
 
 use utils::data::Data;
-use utils::xor::xor;
+use utils::padding;
 
 /// Algorithms that can be used for the encryption and decryption of a single block.
 pub enum Algorithms {
-    /// The AES algorithm.
+    /// The AES algorithm, using the AES-NI instructions if the processor supports them.
     Aes,
+    /// The AES algorithm, always using the portable implementation based on lookup tables.
+    AesPortable,
+    /// The AES algorithm, using a slower bitsliced implementation which runs in constant time
+    /// and makes no memory accesses that depend on the key or the data, unlike the lookup tables
+    /// of `AesPortable`.
+    AesConstantTime,
+    /// The DES algorithm, with an 8-byte key.
+    Des,
+    /// The Triple-DES algorithm in EDE form, with a 16-byte (two-key) or 24-byte (three-key) key.
+    TripleDes,
+    /// The Blowfish algorithm, with a key of between 4 and 56 bytes.
+    Blowfish,
     /// A dummy cipher which takes blocks of the given size and does no encryption or decryption.
     Null(usize),
 }
 
+impl Algorithms {
+    /// Returns a single-block cipher using this algorithm with the given key, or a description
+    /// of the problem if the key is not valid for this algorithm.
+    pub fn cipher(&self, key: &Data) -> Result<Box<dyn Cipher>, String> {
+        match *self {
+            Algorithms::Aes => {
+                match aes::AesCipher::new(key.bytes()) {
+                    Ok(aes) => Ok(Box::new(aes)),
+                    Err(err) => Err(format!("{}", err)),
+                }
+            }
+            Algorithms::AesPortable => {
+                match aes::AesCipher::new_portable(key.bytes()) {
+                    Ok(aes) => Ok(Box::new(aes)),
+                    Err(err) => Err(format!("{}", err)),
+                }
+            }
+            Algorithms::AesConstantTime => {
+                match aes_bitsliced::BitslicedAesCipher::new(key.bytes()) {
+                    Ok(aes) => Ok(Box::new(aes)),
+                    Err(err) => Err(format!("{}", err)),
+                }
+            }
+            Algorithms::Des => {
+                match des::DesCipher::new(key.bytes()) {
+                    Ok(des) => Ok(Box::new(des)),
+                    Err(err) => Err(format!("{}", err)),
+                }
+            }
+            Algorithms::TripleDes => {
+                match des::TripleDesCipher::new(key.bytes()) {
+                    Ok(des) => Ok(Box::new(des)),
+                    Err(err) => Err(format!("{}", err)),
+                }
+            }
+            Algorithms::Blowfish => {
+                match blowfish::blowfish(key.bytes()) {
+                    Ok(blowfish) => Ok(Box::new(blowfish)),
+                    Err(err) => Err(format!("{}", err)),
+                }
+            }
+            Algorithms::Null(size) => Ok(Box::new(null::NullCipher::new(size))),
+        }
+    }
+}
+
 /// Block cipher modes of operation.
 pub enum OperationModes {
     /// Electronic codebook (ECB) mode.
     Ecb,
     /// Cipher block chaining (CBC) mode, including initilisation vector.
     Cbc(Data),
+    /// Output feedback (OFB) mode, including initialisation vector.
+    Ofb(Data),
+    /// Cipher block chaining mode with ciphertext stealing, including initialisation vector and
+    /// the ordering of the last two blocks. The ciphertext is the same length as the plaintext,
+    /// which must be at least one block long, and the padding scheme is not used.
+    CbcCs {
+        /// The initialisation vector.
+        iv: Data,
+        /// The ordering of the last two ciphertext blocks.
+        variant: StealingVariants,
+    },
+    /// Cipher feedback (CFB) mode with a full-block segment size, including initialisation
+    /// vector.
+    Cfb(Data),
+    /// Cipher feedback (CFB) mode with an 8-bit segment size, including initialisation vector.
+    Cfb8(Data),
+    /// Counter (CTR) mode, including nonce and the layout of the counter block.
+    Ctr {
+        /// The nonce which forms the fixed part of each counter block.
+        nonce: Data,
+        /// The way in which the nonce and block counter are combined into a counter block.
+        counter_format: CounterFormats,
+    },
+}
+
+/// Layouts of the counter block used in CTR mode.
+pub enum CounterFormats {
+    /// The nonce, followed by a 64-bit little-endian block counter starting from zero. For a
+    /// 16-byte block, the nonce must be 8 bytes long.
+    LittleEndian64,
+    /// The nonce is the initial counter block, and is incremented as a single big-endian integer
+    /// for each subsequent block, as in NIST SP 800-38A. The nonce must be one block long.
+    BigEndian128,
+}
+
+/// Orderings of the last two ciphertext blocks in CBC mode with ciphertext stealing, as in the
+/// addendum to NIST SP 800-38A.
+///
+/// In each case, the message is encrypted under CBC mode with the last block padded with zeroes,
+/// and the padding bytes are dropped from the penultimate ciphertext block, since they can be
+/// recovered when decrypting the last block.
+pub enum StealingVariants {
+    /// CBC-CS1, where the truncated penultimate block comes before the last block.
+    Cs1,
+    /// CBC-CS2, which is the same as CBC-CS3 unless the message is a whole number of blocks, in
+    /// which case it is the same as CBC.
+    Cs2,
+    /// CBC-CS3, where the last two blocks are always swapped, as used by Kerberos.
+    Cs3,
+}
+
+impl StealingVariants {
+    /// Returns whether the last two ciphertext blocks are swapped, given whether the message is
+    /// a whole number of blocks.
+    fn swaps_blocks(&self, whole_blocks: bool) -> bool {
+        match *self {
+            StealingVariants::Cs1 => false,
+            StealingVariants::Cs2 => !whole_blocks,
+            StealingVariants::Cs3 => true,
+        }
+    }
 }
 
 /// Block cipher padding schemes.
+///
+/// See `utils::padding` for the details of each scheme.
 pub enum PaddingSchemes {
+    /// No padding - the data must already be a multiple of the block size.
+    NoPadding,
     /// PKCS#7 padding.
     Pkcs7,
+    /// Zero padding, which is ambiguous if the data ends with a zero byte.
+    Zero,
+    /// ANSI X.923 padding.
+    AnsiX923,
+    /// ISO 10126 padding, which uses random filler bytes.
+    Iso10126,
+    /// ISO/IEC 7816-4 padding.
+    Iso7816,
 }
 
 /// Errors that can arise as a result of encrypting a piece of data.
 pub enum EncryptError {
     /// The initlisation vector was of the wrong length.
     IVLength,
+    /// The data was of an invalid size.
+    DataLength,
+    /// The operation is not supported by the mode of operation in use.
+    Mode,
 }
 
 /// Errors that can arise as a result of decrypting a piece of data.
@@ -45,6 +191,12 @@ pub enum DecryptError {
     DataLength,
     /// The decrypted data had invalid padding.
     Padding,
+    /// The authentication tag did not match the data.
+    Authentication,
+    /// The integrity check value recovered when unwrapping a key was wrong.
+    Integrity,
+    /// The operation is not supported by the mode of operation in use.
+    Mode,
 }
 
 impl fmt::Display for EncryptError {
@@ -52,6 +204,8 @@ impl fmt::Display for EncryptError {
         use self::EncryptError::*;
         match *self {
             IVLength => write!(f, "Initialisation vector has the wrong size"),
+            DataLength => write!(f, "The given data has an invalid length"),
+            Mode => write!(f, "The operation is not supported by this mode of operation"),
         }
     }
 }
@@ -67,6 +221,8 @@ impl error::Error for EncryptError {
         use self::EncryptError::*;
         match *self {
             IVLength => "invalid iv length",
+            DataLength => "invalid data length",
+            Mode => "unsupported mode of operation",
         }
     }
 }
@@ -78,6 +234,9 @@ impl fmt::Display for DecryptError {
             IVLength => write!(f, "Initialisation vector has the wrong size"),
             DataLength => write!(f, "The given data has an invalid length"),
             Padding => write!(f, "The decrypted data has invalid padding"),
+            Authentication => write!(f, "The authentication tag does not match the data"),
+            Integrity => write!(f, "The unwrapped key failed its integrity check"),
+            Mode => write!(f, "The operation is not supported by this mode of operation"),
         }
     }
 }
@@ -95,29 +254,45 @@ impl error::Error for DecryptError {
             IVLength => "invalid iv length",
             DataLength => "invalid data length",
             Padding => "invalid padding",
+            Authentication => "authentication failed",
+            Integrity => "integrity check failed",
+            Mode => "unsupported mode of operation",
         }
     }
 }
 
 /// Trait for encrypting and decrypting a single block of bytes, to be used as the core of a
 /// block cipher.
-trait Cipher {
-    /// Encrypt a single block of bytes.
-    fn encrypt(&self, input: &[u8]) -> Vec<u8>;
-    /// Decrypt a single block of bytes.
-    fn decrypt(&self, input: &[u8]) -> Vec<u8>;
+///
+/// Implementations can be plugged into the modes of operation and padding schemes provided by
+/// `BlockCipher` using `BlockCipher::from_cipher`. Ciphers must be shareable between threads so
+/// that ECB mode can encrypt and decrypt blocks in parallel.
+pub trait Cipher: Send + Sync {
+    /// Encrypt a single block of bytes in place.
+    fn encrypt_block(&self, block: &mut [u8]);
+    /// Decrypt a single block of bytes in place.
+    fn decrypt_block(&self, block: &mut [u8]);
     /// Return the block size used by this cipher.
     fn block_size(&self) -> usize;
 }
 
+/// XORs the second slice into the first, stopping at the end of the shorter of the two.
+fn xor_in_place(target: &mut [u8], other: &[u8]) {
+    for (x, y) in target.iter_mut().zip(other.iter()) {
+        *x ^= *y;
+    }
+}
+
 /// A generic block cipher encryptor and decryptor.
 pub struct BlockCipher {
     /// The cipher used to encrypt and decrypt single blocks.
-    cipher: Box<Cipher>,
+    cipher: Box<dyn Cipher>,
     /// The operation mode for this block cipher.
     mode: OperationModes,
     /// The padding scheme for this block cipher.
     padding: PaddingSchemes,
+    /// The number of threads used to encrypt and decrypt in ECB mode.
+    threads: usize,
 }
 
 impl BlockCipher {
@@ -128,64 +303,147 @@ impl BlockCipher {
                padding: PaddingSchemes,
                key: &Data)
                -> Result<BlockCipher, String> {
-        match algorithm {
-            Algorithms::Aes => {
-                match aes::AesCipher::new(key.bytes()) {
-                    Ok(aes) => {
-                        Ok(BlockCipher {
-                            cipher: Box::new(aes),
-                            mode: mode,
-                            padding: padding,
-                        })
-                    }
-                    Err(err) => Err(format!("{}", err)),
-                }
-            }
-            Algorithms::Null(size) => {
-                Ok(BlockCipher {
-                    cipher: Box::new(null::NullCipher::new(size)),
-                    mode: mode,
-                    padding: padding,
-                })
-            }
+        Ok(BlockCipher::from_cipher(algorithm.cipher(key)?, mode, padding))
+    }
+
+    /// Returns a new BlockCipher which uses the given single-block cipher, operation mode and
+    /// padding scheme for encryption and decryption of blocks.
+    ///
+    /// This allows any implementation of `Cipher` to be used with the modes of operation and
+    /// padding schemes provided here, not just those listed in `Algorithms`.
+    pub fn from_cipher(cipher: Box<dyn Cipher>,
+                       mode: OperationModes,
+                       padding: PaddingSchemes)
+                       -> BlockCipher {
+        BlockCipher {
+            cipher,
+            mode,
+            padding,
+            threads: 1,
         }
     }
 
+    /// Sets the number of threads used to encrypt and decrypt in ECB mode, which is one unless
+    /// set otherwise. Each thread works on a contiguous run of blocks, sharing a single key
+    /// schedule, and the results are identical to those of a single thread. Other modes of
+    /// operation ignore this setting.
+    pub fn with_threads(mut self, threads: usize) -> BlockCipher {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Returns the size of the blocks used by the underlying cipher.
+    pub fn block_size(&self) -> usize {
+        self.cipher.block_size()
+    }
+
     /// Encrypts the given input data using the given mode of operation.
+    ///
+    /// The padding scheme is not used by the stream modes OFB, CFB and CTR, which can encrypt data
+    /// of any length.
     pub fn encrypt(&self, input: &Data) -> Result<Data, EncryptError> {
-        let data = match self.padding {
-            PaddingSchemes::Pkcs7 => self.pkcs7_pad(input),
-        };
         match self.mode {
-            OperationModes::Ecb => self.ecb_encrypt(&data),
-            OperationModes::Cbc(ref iv) => self.cbc_encrypt(&data, iv),
+            OperationModes::Ecb => self.ecb_encrypt(&self.pad(input)?),
+            OperationModes::Cbc(ref iv) => self.cbc_encrypt(&self.pad(input)?, iv),
+            OperationModes::CbcCs { ref iv, ref variant } => {
+                self.cbc_cs_encrypt(input, iv, variant)
+            }
+            OperationModes::Ofb(ref iv) => self.ofb_encrypt(input, iv),
+            OperationModes::Cfb(ref iv) => self.cfb_encrypt(input, iv, self.cipher.block_size()),
+            OperationModes::Cfb8(ref iv) => self.cfb_encrypt(input, iv, 1),
+            OperationModes::Ctr { ref nonce, ref counter_format } => {
+                self.ctr_encrypt(input, nonce, counter_format)
+            }
         }
     }
 
     /// Decrypts the given input data using the given mode of operation.
     pub fn decrypt(&self, input: &Data) -> Result<Data, DecryptError> {
-        let data = try!(match self.mode {
-            OperationModes::Ecb => self.ecb_decrypt(input),
-            OperationModes::Cbc(ref iv) => self.cbc_decrypt(input, iv),
-        });
-        match self.padding {
-            PaddingSchemes::Pkcs7 => self.pkcs7_unpad(&data),
+        match self.mode {
+            OperationModes::Ecb => self.unpad(&self.ecb_decrypt(input)?),
+            OperationModes::Cbc(ref iv) => self.unpad(&self.cbc_decrypt(input, iv)?),
+            OperationModes::CbcCs { ref iv, ref variant } => {
+                self.cbc_cs_decrypt(input, iv, variant)
+            }
+            OperationModes::Ofb(ref iv) => self.ofb_decrypt(input, iv),
+            OperationModes::Cfb(ref iv) => self.cfb_decrypt(input, iv, self.cipher.block_size()),
+            OperationModes::Cfb8(ref iv) => self.cfb_decrypt(input, iv, 1),
+            OperationModes::Ctr { ref nonce, ref counter_format } => {
+                self.ctr_decrypt(input, nonce, counter_format)
+            }
         }
     }
 
-    /// Encrypts the given data using ECB mode.
-    fn ecb_encrypt(&self, data: &Data) -> Result<Data, EncryptError> {
+    /// Edits the given ciphertext, replacing the plaintext starting at the given byte offset with
+    /// the new plaintext, and returns the resulting ciphertext.
+    ///
+    /// Only the part of the keystream covering the new plaintext is generated, so this is cheap
+    /// even for edits deep into a long ciphertext. The edit may extend past the end of the
+    /// ciphertext, but must not start after it. This is only supported by CTR mode.
+    pub fn edit(&self,
+                ciphertext: &Data,
+                offset: usize,
+                new_plaintext: &Data)
+                -> Result<Data, EncryptError> {
+        match self.mode {
+            OperationModes::Ctr { ref nonce, ref counter_format } => {
 
-        // Somewhere to store the resulting encrypted message.
-        let mut output = Vec::with_capacity(data.len());
+                // Check that the nonce has the right length, and that the edit starts within the
+                // existing ciphertext.
+                if !self.ctr_nonce_valid(nonce, counter_format) {
+                    return Err(EncryptError::IVLength);
+                }
+                if offset > ciphertext.len() {
+                    return Err(EncryptError::DataLength);
+                }
 
-        // Iterate over the data, one block at a time, encrypting them, and storing the results.
-        let mut ix = 0;
-        while ix + self.cipher.block_size() <= data.len() {
-            let in_block = &data.bytes()[ix..ix + self.cipher.block_size()];
-            output.extend_from_slice(&self.cipher.encrypt(in_block));
-            ix += self.cipher.block_size();
+                // Encrypt the new plaintext at the right position in the keystream, and splice
+                // it into the ciphertext.
+                let edited = self.ctr_xor(new_plaintext, nonce, counter_format, offset);
+                let mut output = ciphertext.bytes()[..offset].to_vec();
+                output.extend_from_slice(edited.bytes());
+                if offset + edited.len() < ciphertext.len() {
+                    output.extend_from_slice(&ciphertext.bytes()[offset + edited.len()..]);
+                }
+                Ok(Data::from_bytes(output))
+            }
+            _ => Err(EncryptError::Mode),
         }
+    }
+
+    /// Returns the given number of bytes of keystream, starting at the given byte offset.
+    ///
+    /// Every message encrypted under the same key and nonce is XORed with this same keystream,
+    /// which is what makes nonce reuse fatal. This is only supported by CTR mode.
+    pub fn keystream(&self, offset: usize, len: usize) -> Result<Data, EncryptError> {
+        match self.mode {
+            OperationModes::Ctr { ref nonce, ref counter_format } => {
+                if !self.ctr_nonce_valid(nonce, counter_format) {
+                    return Err(EncryptError::IVLength);
+                }
+                Ok(self.ctr_xor(&Data::from_bytes(vec![0; len]), nonce, counter_format, offset))
+            }
+            _ => Err(EncryptError::Mode),
+        }
+    }
+
+    /// Pads the given data using the padding scheme of this block cipher.
+    fn pad(&self, data: &Data) -> Result<Data, EncryptError> {
+        padding::pad(data, self.cipher.block_size(), &self.padding)
+    }
+
+    /// Removes the padding from the given data using the padding scheme of this block cipher.
+    fn unpad(&self, data: &Data) -> Result<Data, DecryptError> {
+        padding::unpad(data, self.cipher.block_size(), &self.padding)
+    }
+
+    /// Encrypts the given data using ECB mode.
+    fn ecb_encrypt(&self, data: &Data) -> Result<Data, EncryptError> {
+
+        // Copy the whole blocks of the message into the output, and encrypt them in place.
+        let block_size = self.cipher.block_size();
+        let mut output = data.bytes()[..data.len() - data.len() % block_size].to_vec();
+        self.ecb_transform(&mut output, false);
 
         Ok(Data::from_bytes(output))
     }
@@ -198,42 +456,68 @@ impl BlockCipher {
             return Err(DecryptError::DataLength);
         }
 
-        // Somewhere to store the resulting decrypted message.
-        let mut output = Vec::with_capacity(data.len());
+        // Copy the message into the output, and decrypt it in place.
+        let mut output = data.bytes().to_vec();
+        self.ecb_transform(&mut output, true);
 
-        // Iterate over the data, one block at a time, decrypting them, and storing the results.
-        let mut ix = 0;
-        while ix < data.len() {
-            let in_block = &data.bytes()[ix..ix + self.cipher.block_size()];
-            output.extend_from_slice(&self.cipher.decrypt(in_block));
-            ix += self.cipher.block_size();
+        Ok(Data::from_bytes(output))
+    }
+
+    /// Encrypts or decrypts a whole number of blocks in place, independently of one another. If
+    /// more than one thread is configured, the blocks are split into contiguous runs, one for
+    /// each thread.
+    fn ecb_transform(&self, data: &mut [u8], decrypt: bool) {
+        let block_size = self.cipher.block_size();
+        let cipher = &*self.cipher;
+        let transform = |run: &mut [u8]| {
+            for block in run.chunks_mut(block_size) {
+                if decrypt {
+                    cipher.decrypt_block(block);
+                } else {
+                    cipher.encrypt_block(block);
+                }
+            }
+        };
+
+        let blocks = data.len() / block_size;
+        if self.threads == 1 || blocks < 2 {
+            transform(data);
+            return;
         }
 
-        Ok(Data::from_bytes(output))
+        let run_len = blocks.div_ceil(self.threads) * block_size;
+        thread::scope(|scope| {
+            for run in data.chunks_mut(run_len) {
+                scope.spawn(move || transform(run));
+            }
+        });
     }
 
     /// Encrypts the given data using CBC mode.
     fn cbc_encrypt(&self, data: &Data, iv: &Data) -> Result<Data, EncryptError> {
 
         // Check that the initialisation vector has the right length.
-        if iv.len() != self.cipher.block_size() {
+        let block_size = self.cipher.block_size();
+        if iv.len() != block_size {
             return Err(EncryptError::IVLength);
         }
 
-        // Somewhere to store the resulting encrypted message.
-        let mut output = Vec::with_capacity(data.len());
-
-        // Iterate over the data, one block at a time, XORing with the previous ciphertext block,
-        // then encrypting.
+        // Copy the whole blocks of the message into the output, and encrypt them in place, one
+        // block at a time, after XORing with the previous ciphertext block.
+        let mut output = data.bytes()[..data.len() - data.len() % block_size].to_vec();
         let mut ix = 0;
-        let mut last_out_block = iv.clone();
-        while ix + self.cipher.block_size() <= data.len() {
-            let in_block = data.slice(ix, ix + self.cipher.block_size());
-            let xor_block = xor(&in_block, &last_out_block);
-            let out_block = self.cipher.encrypt(xor_block.bytes());
-            output.extend_from_slice(&out_block);
-            last_out_block = Data::from_bytes(out_block);
-            ix += self.cipher.block_size();
+        while ix < output.len() {
+            {
+                let (previous, rest) = output.split_at_mut(ix);
+                let block = &mut rest[..block_size];
+                if ix == 0 {
+                    xor_in_place(block, iv.bytes());
+                } else {
+                    xor_in_place(block, &previous[ix - block_size..]);
+                }
+                self.cipher.encrypt_block(block);
+            }
+            ix += block_size;
         }
 
         Ok(Data::from_bytes(output))
@@ -243,59 +527,620 @@ impl BlockCipher {
     fn cbc_decrypt(&self, data: &Data, iv: &Data) -> Result<Data, DecryptError> {
 
         // Check that the initialisation vector has the right length.
-        if iv.len() != self.cipher.block_size() {
+        let block_size = self.cipher.block_size();
+        if iv.len() != block_size {
             return Err(DecryptError::IVLength);
         }
 
         // Check that the data itself is of a valid length.
-        if data.len() % self.cipher.block_size() != 0 {
+        if data.len() % block_size != 0 {
             return Err(DecryptError::DataLength);
         }
 
-        // Somewhere to store the resulting decrypted message.
-        let mut output = Vec::with_capacity(data.len());
+        // Copy the message into the output, and decrypt it in place, one block at a time, then
+        // XOR with the previous ciphertext block from the input.
+        let mut output = data.bytes().to_vec();
+        for (ix, block) in output.chunks_mut(block_size).enumerate() {
+            self.cipher.decrypt_block(block);
+            if ix == 0 {
+                xor_in_place(block, iv.bytes());
+            } else {
+                xor_in_place(block, &data.bytes()[(ix - 1) * block_size..ix * block_size]);
+            }
+        }
 
-        // Iterate over the data, one block at a time, decrypting the block and then XORing with
-        // the previous ciphertext block.
-        let mut ix = 0;
-        let mut last_in_block = iv.clone();
-        while ix + self.cipher.block_size() <= data.len() {
-            let in_block = data.slice(ix, ix + self.cipher.block_size());
-            let xor_block = self.cipher.decrypt(in_block.bytes());
-            let out_block = xor(&Data::from_bytes(xor_block), &last_in_block);
-            output.extend_from_slice(out_block.bytes());
-            last_in_block = in_block;
-            ix += self.cipher.block_size();
+        Ok(Data::from_bytes(output))
+    }
+
+    /// Encrypts the given data using CBC mode with ciphertext stealing.
+    fn cbc_cs_encrypt(&self,
+                      data: &Data,
+                      iv: &Data,
+                      variant: &StealingVariants)
+                      -> Result<Data, EncryptError> {
+
+        // Check that there is at least one block of data. A single block is just encrypted
+        // under CBC mode.
+        let block_size = self.cipher.block_size();
+        if data.len() < block_size {
+            return Err(EncryptError::DataLength);
+        } else if data.len() == block_size {
+            return self.cbc_encrypt(data, iv);
+        }
+
+        // Encrypt under CBC mode, with the last block padded with zeroes.
+        let mut padded = data.bytes().to_vec();
+        padded.resize(data.len().div_ceil(block_size) * block_size, 0);
+        let mut output = self.cbc_encrypt(&Data::from_bytes(padded), iv)?.bytes().to_vec();
+
+        // Drop the padding from the penultimate block, and put the last two blocks in order.
+        let penultimate_start = output.len() - 2 * block_size;
+        let tail_len = data.len() - penultimate_start - block_size;
+        let last = output.split_off(penultimate_start + block_size);
+        output.truncate(penultimate_start + tail_len);
+        if variant.swaps_blocks(tail_len == block_size) {
+            let stolen = output.split_off(penultimate_start);
+            output.extend_from_slice(&last);
+            output.extend_from_slice(&stolen);
+        } else {
+            output.extend_from_slice(&last);
         }
 
         Ok(Data::from_bytes(output))
     }
 
-    /// Pads the given data using PKCS#7
-    fn pkcs7_pad(&self, data: &Data) -> Data {
+    /// Decrypts the given data using CBC mode with ciphertext stealing.
+    fn cbc_cs_decrypt(&self,
+                      data: &Data,
+                      iv: &Data,
+                      variant: &StealingVariants)
+                      -> Result<Data, DecryptError> {
+
+        // Check that there is at least one block of data. A single block is just decrypted
+        // under CBC mode.
+        let block_size = self.cipher.block_size();
+        if data.len() < block_size {
+            return Err(DecryptError::DataLength);
+        } else if data.len() == block_size {
+            return self.cbc_decrypt(data, iv);
+        }
+
+        // Find the truncated penultimate block and the last block.
+        let bytes = data.bytes();
+        let penultimate_start = (data.len().div_ceil(block_size) - 2) * block_size;
+        let tail_len = data.len() - penultimate_start - block_size;
+        let (stolen, last) = if variant.swaps_blocks(tail_len == block_size) {
+            let (last, stolen) = bytes[penultimate_start..].split_at(block_size);
+            (stolen, last)
+        } else {
+            bytes[penultimate_start..].split_at(tail_len)
+        };
+
+        // Decrypting the last block gives the zero-padded last plaintext block XORed with the
+        // full penultimate ciphertext block, so the end of the decrypted block is exactly the
+        // part of the penultimate block which was dropped. Restore it, and decrypt the whole
+        // message under CBC mode, before removing the padding.
+        let mut decrypted_last = last.to_vec();
+        self.cipher.decrypt_block(&mut decrypted_last);
+        let mut ciphertext = bytes[..penultimate_start].to_vec();
+        ciphertext.extend_from_slice(stolen);
+        ciphertext.extend_from_slice(&decrypted_last[tail_len..]);
+        ciphertext.extend_from_slice(last);
+        let mut output = self.cbc_decrypt(&Data::from_bytes(ciphertext), iv)?.bytes().to_vec();
+        output.truncate(data.len());
+
+        Ok(Data::from_bytes(output))
+    }
+
+    /// Encrypts the given data using OFB mode.
+    fn ofb_encrypt(&self, data: &Data, iv: &Data) -> Result<Data, EncryptError> {
+
+        // Check that the initialisation vector has the right length.
+        if iv.len() != self.cipher.block_size() {
+            return Err(EncryptError::IVLength);
+        }
+
+        Ok(self.ofb_xor(data, iv))
+    }
+
+    /// Decrypts the given data using OFB mode.
+    fn ofb_decrypt(&self, data: &Data, iv: &Data) -> Result<Data, DecryptError> {
+
+        // Check that the initialisation vector has the right length.
+        if iv.len() != self.cipher.block_size() {
+            return Err(DecryptError::IVLength);
+        }
+
+        Ok(self.ofb_xor(data, iv))
+    }
+
+    /// XORs the given data with the OFB keystream generated from the given initialisation
+    /// vector. Since this is its own inverse, it serves for both encryption and decryption.
+    fn ofb_xor(&self, data: &Data, iv: &Data) -> Data {
+
+        // Copy the message into the output, and XOR it in place, one block at a time, with the
+        // keystream formed by repeatedly encrypting the initialisation vector. The final block may
+        // be only partially used.
+        let mut output = data.bytes().to_vec();
+        let mut keystream = iv.bytes().to_vec();
+        for block in output.chunks_mut(self.cipher.block_size()) {
+            self.cipher.encrypt_block(&mut keystream);
+            xor_in_place(block, &keystream);
+        }
+
+        Data::from_bytes(output)
+    }
+
+    /// Encrypts the given data using CFB mode with the given segment size in bytes.
+    fn cfb_encrypt(&self, data: &Data, iv: &Data, segment: usize) -> Result<Data, EncryptError> {
+
+        // Check that the initialisation vector has the right length.
+        if iv.len() != self.cipher.block_size() {
+            return Err(EncryptError::IVLength);
+        }
+
+        Ok(self.cfb_transform(data, iv, segment, false))
+    }
+
+    /// Decrypts the given data using CFB mode with the given segment size in bytes.
+    fn cfb_decrypt(&self, data: &Data, iv: &Data, segment: usize) -> Result<Data, DecryptError> {
+
+        // Check that the initialisation vector has the right length.
+        if iv.len() != self.cipher.block_size() {
+            return Err(DecryptError::IVLength);
+        }
+
+        Ok(self.cfb_transform(data, iv, segment, true))
+    }
+
+    /// Encrypts or decrypts the given data using CFB mode with the given segment size in bytes.
+    fn cfb_transform(&self, data: &Data, iv: &Data, segment: usize, decrypt: bool) -> Data {
+
+        // Copy the message into the output, and XOR it in place, one segment at a time, with the
+        // encrypted shift register, then shift the ciphertext segment into the register. The final
+        // segment may be only partially used.
+        let block_size = self.cipher.block_size();
+        let mut output = data.bytes().to_vec();
+        let mut register = iv.bytes().to_vec();
+        let mut keystream = vec![0; block_size];
+        for (ix, out_segment) in output.chunks_mut(segment).enumerate() {
+            keystream.copy_from_slice(&register);
+            self.cipher.encrypt_block(&mut keystream);
+            xor_in_place(out_segment, &keystream);
+
+            let ciphertext = if decrypt {
+                &data.bytes()[ix * segment..ix * segment + out_segment.len()]
+            } else {
+                &*out_segment
+            };
+            register.copy_within(segment.., 0);
+            register[block_size - segment..block_size - segment + ciphertext.len()]
+                .copy_from_slice(ciphertext);
+        }
+
+        Data::from_bytes(output)
+    }
 
-        // Work out the value of the padding bytes.
-        let pad = self.cipher.block_size() - data.len() % self.cipher.block_size();
+    /// Encrypts the given data using CTR mode.
+    fn ctr_encrypt(&self,
+                   data: &Data,
+                   nonce: &Data,
+                   counter_format: &CounterFormats)
+                   -> Result<Data, EncryptError> {
 
-        // Construct a new Data with the padding included.
-        let mut new_bytes = data.bytes().to_vec();
-        for _ in 0..pad {
-            new_bytes.push(pad as u8);
+        // Check that the nonce has the right length.
+        if !self.ctr_nonce_valid(nonce, counter_format) {
+            return Err(EncryptError::IVLength);
         }
-        Data::from_bytes(new_bytes)
+
+        Ok(self.ctr_xor(data, nonce, counter_format, 0))
     }
 
-    /// Unpads the given data using PKCS#7
-    fn pkcs7_unpad(&self, data: &Data) -> Result<Data, DecryptError> {
+    /// Decrypts the given data using CTR mode.
+    fn ctr_decrypt(&self,
+                   data: &Data,
+                   nonce: &Data,
+                   counter_format: &CounterFormats)
+                   -> Result<Data, DecryptError> {
+
+        // Check that the nonce has the right length.
+        if !self.ctr_nonce_valid(nonce, counter_format) {
+            return Err(DecryptError::IVLength);
+        }
+
+        Ok(self.ctr_xor(data, nonce, counter_format, 0))
+    }
+
+    /// Checks whether the given nonce has a valid length for the given counter format.
+    fn ctr_nonce_valid(&self, nonce: &Data, counter_format: &CounterFormats) -> bool {
+        match *counter_format {
+            CounterFormats::LittleEndian64 => nonce.len() + 8 == self.cipher.block_size(),
+            CounterFormats::BigEndian128 => nonce.len() == self.cipher.block_size(),
+        }
+    }
+
+    /// Writes the counter block for the block at the given index in the keystream into the given
+    /// buffer.
+    fn ctr_counter_block(&self,
+                         nonce: &Data,
+                         counter_format: &CounterFormats,
+                         index: u64,
+                         block: &mut [u8]) {
+        block[..nonce.len()].copy_from_slice(nonce.bytes());
+        match *counter_format {
+            CounterFormats::LittleEndian64 => {
+                block[nonce.len()..].copy_from_slice(&index.to_le_bytes());
+            }
+            CounterFormats::BigEndian128 => {
+                // Add the index to the nonce, treating both as big-endian integers.
+                let mut carry = index as u128;
+                for byte in block.iter_mut().rev() {
+                    carry += *byte as u128;
+                    *byte = carry as u8;
+                    carry >>= 8;
+                }
+            }
+        }
+    }
+
+    /// XORs the given data with the CTR keystream generated from the given nonce, starting at the
+    /// given byte offset into the keystream. Since this is its own inverse, it serves for both
+    /// encryption and decryption.
+    fn ctr_xor(&self,
+               data: &Data,
+               nonce: &Data,
+               counter_format: &CounterFormats,
+               offset: usize)
+               -> Data {
+
+        // Copy the message into the output, and XOR it in place with the encrypted counter blocks.
+        // The first and last keystream blocks may be only partially used.
+        let block_size = self.cipher.block_size();
+        let mut output = data.bytes().to_vec();
+        let mut keystream = vec![0; block_size];
+        let mut ix = 0;
+        while ix < output.len() {
+            let position = offset + ix;
+            let skip = position % block_size;
+            self.ctr_counter_block(nonce,
+                                   counter_format,
+                                   (position / block_size) as u64,
+                                   &mut keystream);
+            self.cipher.encrypt_block(&mut keystream);
+            xor_in_place(&mut output[ix..], &keystream[skip..]);
+            ix += block_size - skip;
+        }
+
+        Data::from_bytes(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Instant;
+
+    use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes, CounterFormats,
+                       StealingVariants, Cipher};
+    use utils::data::Data;
+    use utils::xor;
+
+    /// The key used by the NIST SP 800-38A example vectors.
+    const NIST_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+
+    /// The plaintext used by the NIST SP 800-38A example vectors.
+    const NIST_PLAINTEXT: &str = "6bc1bee22e409f96e93d7e117393172a\
+                                          ae2d8a571e03ac9c9eb76fac45af8e51\
+                                          30c81c46a35ce411e5fbc1191a0a52ef\
+                                          f69f2445df4f9b17ad2b417be66c3710";
+
+    /// Encrypt and decrypt the NIST plaintext using the given mode, and check the ciphertext.
+    fn check_nist_vector(mode: OperationModes, ciphertext: &str) {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let plaintext = Data::from_hex(NIST_PLAINTEXT).unwrap();
+        let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key).unwrap();
+        assert_eq!(block.encrypt(&plaintext).unwrap().to_hex(), ciphertext);
+        let ciphertext = Data::from_hex(ciphertext).unwrap();
+        assert_eq!(block.decrypt(&ciphertext).unwrap().to_hex(), NIST_PLAINTEXT);
+    }
+
+    /// A toy cipher with 4-byte blocks, which adds a key to each byte.
+    struct AddCipher {
+        key: u8,
+    }
+
+    impl Cipher for AddCipher {
+        fn encrypt_block(&self, block: &mut [u8]) {
+            for byte in block.iter_mut() {
+                *byte = byte.wrapping_add(self.key);
+            }
+        }
+
+        fn decrypt_block(&self, block: &mut [u8]) {
+            for byte in block.iter_mut() {
+                *byte = byte.wrapping_sub(self.key);
+            }
+        }
+
+        fn block_size(&self) -> usize {
+            4
+        }
+    }
+
+    #[test]
+    fn custom_cipher() {
+        let iv = Data::from_bytes(vec![1, 2, 3, 4]);
+        let block = BlockCipher::from_cipher(Box::new(AddCipher { key: 3 }),
+                                             OperationModes::Cbc(iv),
+                                             PaddingSchemes::Pkcs7);
+        let ciphertext = block.encrypt(&Data::from_text("hello")).unwrap();
+        assert_eq!(ciphertext.to_hex(), "6c6a726b066c746b");
+        assert_eq!(block.decrypt(&ciphertext).unwrap().to_text(), "hello");
+    }
+
+    #[test]
+    fn ofb_nist_vector() {
+        let iv = Data::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        check_nist_vector(OperationModes::Ofb(iv),
+                          "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+                           9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e");
+    }
+
+    #[test]
+    fn cfb_nist_vector() {
+        let iv = Data::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        check_nist_vector(OperationModes::Cfb(iv),
+                          "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+                           26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6");
+    }
+
+    #[test]
+    fn cfb8_nist_vector() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let iv = Data::from_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let block = BlockCipher::new(Algorithms::Aes,
+                                     OperationModes::Cfb8(iv),
+                                     PaddingSchemes::Pkcs7,
+                                     &key)
+            .unwrap();
+        let plaintext = Data::from_hex("6bc1bee22e409f96e93d7e117393172aae2d").unwrap();
+        let ciphertext = block.encrypt(&plaintext).unwrap();
+        assert_eq!(ciphertext.to_hex(), "3b79424c9c0dd436bace9e0ed4586a4f32b9");
+        assert_eq!(block.decrypt(&ciphertext).unwrap().to_hex(), plaintext.to_hex());
+    }
+
+    #[test]
+    fn feedback_modes_check_iv_length() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let plaintext = Data::from_hex(NIST_PLAINTEXT).unwrap();
+        let iv = Data::from_bytes(vec![0; 15]);
+        for mode in [OperationModes::Ofb(iv.clone()),
+                     OperationModes::Cfb(iv.clone()),
+                     OperationModes::Cfb8(iv.clone())] {
+            let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key)
+                .unwrap();
+            assert!(block.encrypt(&plaintext).is_err());
+            assert!(block.decrypt(&plaintext).is_err());
+        }
+    }
+
+    #[test]
+    fn cbc_cs_rfc_3962_vectors() {
+        // The RFC 3962 vectors are for CBC-CS3. The other variants differ only in the order of
+        // the last two blocks.
+        let key = Data::from_text("chicken teriyaki");
+        let plaintext = "I would like the General Gau's Chicken, please, and wonton soup.";
+        let vectors = [(17,
+                        "97c6353568f2bf8cb4d8a580362da7ff7f",
+                        "c6353568f2bf8cb4d8a580362da7ff7f97"),
+                       (31,
+                        "97687268d6ecccc0c07b25e25ecfe5fc00783e0efdb2c1d445d4c8eff7ed22",
+                        "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5"),
+                       (32,
+                        "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8",
+                        "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584"),
+                       (47,
+                        "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5\
+                         b3fffd940c16a18c1b5549d2f838029e",
+                        "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e\
+                         39312523a78662d5be7fcbcc98ebf5"),
+                       (48,
+                        "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8\
+                         9dad8bbb96c4cdc03bc103e1a194bbd8",
+                        "97687268d6ecccc0c07b25e25ecfe5849dad8bbb96c4cdc03bc103e1a194bbd8\
+                         39312523a78662d5be7fcbcc98ebf5a8"),
+                       (64,
+                        "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8\
+                         9dad8bbb96c4cdc03bc103e1a194bbd84807efe836ee89a526730dbc2f7bc840",
+                        "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8\
+                         4807efe836ee89a526730dbc2f7bc8409dad8bbb96c4cdc03bc103e1a194bbd8")];
+        for &(len, cs1, cs3) in &vectors {
+            let input = Data::from_text(&plaintext[..len]);
+            let cs2 = if len % 16 == 0 { cs1 } else { cs3 };
+            let variants = [(StealingVariants::Cs1, cs1),
+                            (StealingVariants::Cs2, cs2),
+                            (StealingVariants::Cs3, cs3)];
+            for (variant, expected) in variants {
+                let mode = OperationModes::CbcCs {
+                    iv: Data::from_bytes(vec![0; 16]),
+                    variant,
+                };
+                let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key)
+                    .unwrap();
+                let ciphertext = block.encrypt(&input).unwrap();
+                assert_eq!(ciphertext.to_hex(), expected);
+                assert_eq!(block.decrypt(&ciphertext).unwrap().to_text(), &plaintext[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn cbc_cs_short_input() {
+        let mode = OperationModes::CbcCs {
+            iv: Data::from_bytes(vec![0; 16]),
+            variant: StealingVariants::Cs3,
+        };
+        let block = BlockCipher::new(Algorithms::Aes,
+                                     mode,
+                                     PaddingSchemes::Pkcs7,
+                                     &Data::from_text("chicken teriyaki"))
+            .unwrap();
+        assert!(block.encrypt(&Data::from_text("Too short")).is_err());
+        assert!(block.decrypt(&Data::from_text("Too short")).is_err());
+
+        // A single block is encrypted exactly as under CBC mode.
+        let ciphertext = block.encrypt(&Data::from_text("I would like the")).unwrap();
+        assert_eq!(ciphertext.len(), 16);
+        assert_eq!(block.decrypt(&ciphertext).unwrap().to_text(), "I would like the");
+    }
+
+    #[test]
+    fn ctr_nist_vector() {
+        let mode = OperationModes::Ctr {
+            nonce: Data::from_hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap(),
+            counter_format: CounterFormats::BigEndian128,
+        };
+        check_nist_vector(mode,
+                          "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff\
+                           5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee");
+    }
+
+    #[test]
+    fn ctr_partial_block() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let mode = OperationModes::Ctr {
+            nonce: Data::from_bytes(vec![0; 8]),
+            counter_format: CounterFormats::LittleEndian64,
+        };
+        let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key).unwrap();
+        let plaintext = Data::from_text("Not a whole number of blocks");
+        let ciphertext = block.encrypt(&plaintext).unwrap();
+        assert_eq!(ciphertext.len(), plaintext.len());
+        assert_eq!(block.decrypt(&ciphertext).unwrap().to_text(), plaintext.to_text());
+    }
+
+    #[test]
+    fn ctr_edit() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let mode = OperationModes::Ctr {
+            nonce: Data::from_bytes(vec![0; 8]),
+            counter_format: CounterFormats::LittleEndian64,
+        };
+        let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key).unwrap();
+        let plaintext = Data::from_text("The quick brown fox jumps over the lazy dog");
+        let ciphertext = block.encrypt(&plaintext).unwrap();
+
+        // Edit the middle of the message, within a single block.
+        let edited = block.edit(&ciphertext, 10, &Data::from_text("green")).unwrap();
+        assert_eq!(block.decrypt(&edited).unwrap().to_text(),
+                   "The quick green fox jumps over the lazy dog");
+
+        // Edit the middle of the message, across the boundary between the first two blocks.
+        let edited = block.edit(&ciphertext, 10, &Data::from_text("green cat")).unwrap();
+        assert_eq!(block.decrypt(&edited).unwrap().to_text(),
+                   "The quick green cat jumps over the lazy dog");
+
+        // Edit past the end of the message.
+        let edited = block.edit(&ciphertext, 40, &Data::from_text("cat and mouse")).unwrap();
+        assert_eq!(block.decrypt(&edited).unwrap().to_text(),
+                   "The quick brown fox jumps over the lazy cat and mouse");
+
+        // The edit cannot start after the end of the message.
+        assert!(block.edit(&ciphertext, 44, &Data::from_text("!")).is_err());
+    }
+
+    #[test]
+    fn ctr_keystream() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let mode = OperationModes::Ctr {
+            nonce: Data::from_bytes(vec![0; 8]),
+            counter_format: CounterFormats::LittleEndian64,
+        };
+        let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key).unwrap();
+
+        // Encryption is XOR with the keystream, and the keystream can start part-way through.
+        let plaintext = Data::from_text("The quick brown fox jumps over the lazy dog");
+        let keystream = block.keystream(0, plaintext.len()).unwrap();
+        assert_eq!(xor::xor(&plaintext, &keystream).to_hex(),
+                   block.encrypt(&plaintext).unwrap().to_hex());
+        assert_eq!(block.keystream(10, 20).unwrap().to_hex(), keystream.slice(10, 30).to_hex());
+
+        // Other modes have no keystream to reuse.
+        let block =
+            BlockCipher::new(Algorithms::Aes, OperationModes::Ecb, PaddingSchemes::Pkcs7, &key)
+                .unwrap();
+        assert!(block.keystream(0, 16).is_err());
+    }
+
+    #[test]
+    fn parallel_ecb() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let serial = BlockCipher::new(Algorithms::Aes,
+                                      OperationModes::Ecb,
+                                      PaddingSchemes::Pkcs7,
+                                      &key)
+            .unwrap();
+        for &threads in &[2, 3, 4, 16] {
+            let parallel = BlockCipher::new(Algorithms::Aes,
+                                            OperationModes::Ecb,
+                                            PaddingSchemes::Pkcs7,
+                                            &key)
+                .unwrap()
+                .with_threads(threads);
+            for &len in &[0, 5, 16, 47, 1000, 4096] {
+                let plaintext = Data::random(len);
+                let ciphertext = parallel.encrypt(&plaintext).unwrap();
+                assert_eq!(ciphertext.to_hex(), serial.encrypt(&plaintext).unwrap().to_hex());
+                assert_eq!(parallel.decrypt(&ciphertext).unwrap().to_hex(), plaintext.to_hex());
+            }
+        }
+    }
 
-        // Remove the last N bytes, where N is the value of the final byte, after first checking
-        // that all of these bytes have value N.
-        let pad = data.bytes()[data.len() - 1] as usize;
-        if data.len() < pad || !(&data.bytes().ends_with(&vec![pad as u8; pad])) {
-            return Err(DecryptError::Padding);
+    /// Measures the throughput of AES in each mode of operation, in MiB/s.
+    ///
+    /// This is ignored by default, as it is slow and only prints its results rather than checking
+    /// them. Run it with `cargo test --release throughput -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn throughput() {
+        let key = Data::from_hex(NIST_KEY).unwrap();
+        let data = Data::random(16 << 20);
+        let modes = vec![("ECB", OperationModes::Ecb),
+                         ("CBC", OperationModes::Cbc(Data::random(16))),
+                         ("CTR",
+                          OperationModes::Ctr {
+                              nonce: Data::random(8),
+                              counter_format: CounterFormats::LittleEndian64,
+                          })];
+        for (name, mode) in modes {
+            let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key)
+                .unwrap();
+            let start = Instant::now();
+            let ciphertext = block.encrypt(&data).unwrap();
+            let encrypt_time = start.elapsed().as_secs_f64();
+            let start = Instant::now();
+            block.decrypt(&ciphertext).unwrap();
+            let decrypt_time = start.elapsed().as_secs_f64();
+            println!("{}: encrypt {:.1} MiB/s, decrypt {:.1} MiB/s",
+                     name,
+                     16.0 / encrypt_time,
+                     16.0 / decrypt_time);
         }
 
-        let new_bytes = &data.bytes()[..data.len() - pad];
-        Ok(Data::from_bytes(new_bytes.to_vec()))
+        let block = BlockCipher::new(Algorithms::Aes,
+                                     OperationModes::Ecb,
+                                     PaddingSchemes::Pkcs7,
+                                     &key)
+            .unwrap()
+            .with_threads(4);
+        let start = Instant::now();
+        let ciphertext = block.encrypt(&data).unwrap();
+        let encrypt_time = start.elapsed().as_secs_f64();
+        let start = Instant::now();
+        block.decrypt(&ciphertext).unwrap();
+        let decrypt_time = start.elapsed().as_secs_f64();
+        println!("ECB (4 threads): encrypt {:.1} MiB/s, decrypt {:.1} MiB/s",
+                 16.0 / encrypt_time,
+                 16.0 / decrypt_time);
     }
 }
@@ -15,15 +15,11 @@ impl NullCipher {
 }
 
 impl Cipher for NullCipher {
-    /// Encrypt a single block of bytes.
-    fn encrypt(&self, input: &[u8]) -> Vec<u8> {
-        input.to_vec()
-    }
+    /// Encrypt a single block of bytes in place.
+    fn encrypt_block(&self, _block: &mut [u8]) {}
 
-    /// Decrypt a single block of bytes.
-    fn decrypt(&self, input: &[u8]) -> Vec<u8> {
-        input.to_vec()
-    }
+    /// Decrypt a single block of bytes in place.
+    fn decrypt_block(&self, _block: &mut [u8]) {}
 
     /// Return the block size of this cipher.
     fn block_size(&self) -> usize {
@@ -0,0 +1,414 @@
+//! Incremental encryption and decryption of data which arrives a piece at a time.
+//!
+//! An `Encryptor` or `Decryptor` is fed data using `update`, which returns as much output as can
+//! be produced so far, buffering any partial block. Calling `finalize` then deals with the padding
+//! and returns the remaining output. Taken together, the outputs are identical to those of
+//! `BlockCipher::encrypt` and `BlockCipher::decrypt`.
+//!
+//! `CipherWriter` and `CipherReader` wrap these up as `std::io::Write` and `std::io::Read`
+//! adapters. Only ECB and CBC modes are supported.
+
+use std::io;
+use std::io::{Read, Write};
+
+use utils::block::{BlockCipher, OperationModes, EncryptError, DecryptError};
+use utils::data::Data;
+
+/// The size of the chunks in which a `CipherReader` reads from the underlying reader.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// An incremental encryption or decryption process.
+pub trait Transform {
+    /// Process the given input, and return any output which is ready.
+    fn update(&mut self, input: &[u8]) -> Data;
+    /// Process the end of the input, and return the remaining output.
+    fn finish(self) -> io::Result<Data>;
+}
+
+/// An incremental encryptor, created by `BlockCipher::encryptor`.
+pub struct Encryptor<'a> {
+    /// The BlockCipher used to encrypt data.
+    block: &'a BlockCipher,
+    /// Input bytes which do not yet make up a whole block.
+    buffer: Vec<u8>,
+    /// The previous ciphertext block, or the initialisation vector, when using CBC mode.
+    chain: Data,
+}
+
+/// An incremental decryptor, created by `BlockCipher::decryptor`.
+pub struct Decryptor<'a> {
+    /// The BlockCipher used to decrypt data.
+    block: &'a BlockCipher,
+    /// Input bytes which have not yet been decrypted. The final block is always held back here,
+    /// since it may contain padding.
+    buffer: Vec<u8>,
+    /// The previous ciphertext block, or the initialisation vector, when using CBC mode.
+    chain: Data,
+}
+
+impl BlockCipher {
+    /// Returns an `Encryptor` which encrypts data incrementally using this block cipher.
+    ///
+    /// Only ECB and CBC modes are supported.
+    pub fn encryptor(&self) -> Result<Encryptor<'_>, EncryptError> {
+        let chain = match self.mode {
+            OperationModes::Ecb => Data::new(),
+            OperationModes::Cbc(ref iv) => {
+                if iv.len() != self.cipher.block_size() {
+                    return Err(EncryptError::IVLength);
+                }
+                iv.clone()
+            }
+            _ => return Err(EncryptError::Mode),
+        };
+        Ok(Encryptor {
+            block: self,
+            buffer: Vec::new(),
+            chain,
+        })
+    }
+
+    /// Returns a `Decryptor` which decrypts data incrementally using this block cipher.
+    ///
+    /// Only ECB and CBC modes are supported.
+    pub fn decryptor(&self) -> Result<Decryptor<'_>, DecryptError> {
+        let chain = match self.mode {
+            OperationModes::Ecb => Data::new(),
+            OperationModes::Cbc(ref iv) => {
+                if iv.len() != self.cipher.block_size() {
+                    return Err(DecryptError::IVLength);
+                }
+                iv.clone()
+            }
+            _ => return Err(DecryptError::Mode),
+        };
+        Ok(Decryptor {
+            block: self,
+            buffer: Vec::new(),
+            chain,
+        })
+    }
+}
+
+impl<'a> Encryptor<'a> {
+    /// Encrypts the given input, and returns the encryption of all the complete blocks received
+    /// so far which have not already been returned.
+    pub fn update(&mut self, input: &[u8]) -> Data {
+        self.buffer.extend_from_slice(input);
+        let block_size = self.block.cipher.block_size();
+        let ready = self.buffer.len() - self.buffer.len() % block_size;
+        let blocks: Vec<u8> = self.buffer.drain(..ready).collect();
+        self.encrypt_blocks(&Data::from_bytes(blocks))
+    }
+
+    /// Pads and encrypts the remaining input, and returns the final blocks of ciphertext.
+    pub fn finalize(mut self) -> Result<Data, EncryptError> {
+        let remaining = Data::from_bytes(self.buffer.clone());
+        let padded = self.block.pad(&remaining)?;
+        Ok(self.encrypt_blocks(&padded))
+    }
+
+    /// Encrypts a whole number of blocks, continuing the chain from any previous blocks.
+    fn encrypt_blocks(&mut self, blocks: &Data) -> Data {
+        match self.block.mode {
+            OperationModes::Cbc(_) => {
+                let output = self.block.cbc_encrypt(blocks, &self.chain).unwrap();
+                if !output.is_empty() {
+                    self.chain = output.slice(output.len() - self.chain.len(), output.len());
+                }
+                output
+            }
+            _ => self.block.ecb_encrypt(blocks).unwrap(),
+        }
+    }
+}
+
+impl<'a> Decryptor<'a> {
+    /// Decrypts the given input, and returns the decryption of all the complete blocks received
+    /// so far which have not already been returned, except for the final block.
+    pub fn update(&mut self, input: &[u8]) -> Data {
+        self.buffer.extend_from_slice(input);
+        if self.buffer.is_empty() {
+            return Data::new();
+        }
+
+        // Always keep back at least one byte, so that the final block stays in the buffer.
+        let block_size = self.block.cipher.block_size();
+        let ready = (self.buffer.len() - 1) / block_size * block_size;
+        let blocks: Vec<u8> = self.buffer.drain(..ready).collect();
+        self.decrypt_blocks(&Data::from_bytes(blocks)).unwrap()
+    }
+
+    /// Decrypts the final block, removes the padding, and returns the remaining plaintext.
+    pub fn finalize(mut self) -> Result<Data, DecryptError> {
+        let remaining = Data::from_bytes(self.buffer.clone());
+        let decrypted = self.decrypt_blocks(&remaining)?;
+        self.block.unpad(&decrypted)
+    }
+
+    /// Decrypts a whole number of blocks, continuing the chain from any previous blocks.
+    fn decrypt_blocks(&mut self, blocks: &Data) -> Result<Data, DecryptError> {
+        match self.block.mode {
+            OperationModes::Cbc(_) => {
+                let output = self.block.cbc_decrypt(blocks, &self.chain)?;
+                if !blocks.is_empty() {
+                    self.chain = blocks.slice(blocks.len() - self.chain.len(), blocks.len());
+                }
+                Ok(output)
+            }
+            _ => self.block.ecb_decrypt(blocks),
+        }
+    }
+}
+
+impl<'a> Transform for Encryptor<'a> {
+    fn update(&mut self, input: &[u8]) -> Data {
+        Encryptor::update(self, input)
+    }
+
+    fn finish(self) -> io::Result<Data> {
+        self.finalize().map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+}
+
+impl<'a> Transform for Decryptor<'a> {
+    fn update(&mut self, input: &[u8]) -> Data {
+        Decryptor::update(self, input)
+    }
+
+    fn finish(self) -> io::Result<Data> {
+        self.finalize().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// A writer which encrypts or decrypts everything written to it, and writes the result to an
+/// underlying writer.
+///
+/// `finish` must be called once all the data has been written, to deal with the final block.
+pub struct CipherWriter<T: Transform, W: Write> {
+    /// The encryption or decryption process.
+    transform: T,
+    /// The writer to which output is written.
+    inner: W,
+}
+
+impl<T: Transform, W: Write> CipherWriter<T, W> {
+    /// Creates a new CipherWriter which passes data through the given encryptor or decryptor to
+    /// the given writer.
+    pub fn new(transform: T, inner: W) -> CipherWriter<T, W> {
+        CipherWriter { transform, inner }
+    }
+
+    /// Writes the final block of output, and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let output = self.transform.finish()?;
+        self.inner.write_all(output.bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<T: Transform, W: Write> Write for CipherWriter<T, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let output = self.transform.update(buf);
+        self.inner.write_all(output.bytes())?;
+        Ok(buf.len())
+    }
+
+    /// Flushes the underlying writer. Any partial block is still held back until `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader which reads from an underlying reader, and encrypts or decrypts everything read.
+pub struct CipherReader<T: Transform, R: Read> {
+    /// The encryption or decryption process, until the underlying reader is exhausted.
+    transform: Option<T>,
+    /// The reader from which input is read.
+    inner: R,
+    /// Output which has been produced but not yet read.
+    pending: Vec<u8>,
+    /// The position of the next unread byte in `pending`.
+    position: usize,
+}
+
+impl<T: Transform, R: Read> CipherReader<T, R> {
+    /// Creates a new CipherReader which passes data from the given reader through the given
+    /// encryptor or decryptor.
+    pub fn new(transform: T, inner: R) -> CipherReader<T, R> {
+        CipherReader {
+            transform: Some(transform),
+            inner,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<T: Transform, R: Read> Read for CipherReader<T, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+
+        // Read from the underlying reader until some output is ready, or there is no more input.
+        while self.position == self.pending.len() {
+            let transform = match self.transform.take() {
+                Some(transform) => transform,
+                None => return Ok(0),
+            };
+
+            // Put the transform back if the underlying reader fails, so that an error which can be
+            // retried, such as an interruption, is not mistaken for the end of the input.
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            let count = match self.inner.read(&mut chunk) {
+                Ok(count) => count,
+                Err(err) => {
+                    self.transform = Some(transform);
+                    return Err(err);
+                }
+            };
+            let output = if count == 0 {
+                transform.finish()?
+            } else {
+                let mut transform = transform;
+                let output = transform.update(&chunk[..count]);
+                self.transform = Some(transform);
+                output
+            };
+            self.pending = output.bytes().to_vec();
+            self.position = 0;
+        }
+
+        // Copy as much pending output as fits.
+        let count = buf.len().min(self.pending.len() - self.position);
+        buf[..count].copy_from_slice(&self.pending[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io;
+    use std::io::{Read, Write};
+
+    use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes};
+    use utils::block::streaming::{CipherReader, CipherWriter};
+    use utils::data::Data;
+
+    /// Creates an AES block cipher with a fixed key, using the given mode and padding.
+    fn aes(mode: OperationModes, padding: PaddingSchemes) -> BlockCipher {
+        let key = Data::from_text("YELLOW SUBMARINE");
+        BlockCipher::new(Algorithms::Aes, mode, padding, &key).unwrap()
+    }
+
+    /// Check that encrypting and decrypting the given data in chunks of the given size produces
+    /// the same output as the one-shot API.
+    fn check_streaming(block: &BlockCipher, data: &Data, chunk_size: usize) {
+
+        // Encrypt using update and finalize.
+        let mut encryptor = block.encryptor().unwrap();
+        let mut ciphertext = Vec::new();
+        for chunk in data.bytes().chunks(chunk_size) {
+            ciphertext.extend_from_slice(encryptor.update(chunk).bytes());
+        }
+        ciphertext.extend_from_slice(encryptor.finalize().unwrap().bytes());
+        assert_eq!(ciphertext, block.encrypt(data).unwrap().bytes());
+
+        // Decrypt using update and finalize.
+        let mut decryptor = block.decryptor().unwrap();
+        let mut plaintext = Vec::new();
+        for chunk in ciphertext.chunks(chunk_size) {
+            plaintext.extend_from_slice(decryptor.update(chunk).bytes());
+        }
+        plaintext.extend_from_slice(decryptor.finalize().unwrap().bytes());
+        assert_eq!(plaintext, data.bytes());
+    }
+
+    #[test]
+    fn update_and_finalize() {
+        let data = Data::random(1000);
+        for &len in &[0, 1, 15, 16, 17, 32, 999, 1000] {
+            for &chunk_size in &[1, 7, 16, 33, 1000] {
+                let iv = Data::from_bytes(vec![7; 16]);
+                check_streaming(&aes(OperationModes::Ecb, PaddingSchemes::Pkcs7),
+                                &data.slice(0, len),
+                                chunk_size);
+                check_streaming(&aes(OperationModes::Cbc(iv), PaddingSchemes::Iso7816),
+                                &data.slice(0, len),
+                                chunk_size);
+            }
+        }
+    }
+
+    #[test]
+    fn reader_and_writer() {
+        let iv = Data::from_bytes(vec![3; 16]);
+        let block = aes(OperationModes::Cbc(iv), PaddingSchemes::Pkcs7);
+        let data = Data::random(100_000);
+
+        // Encrypt through a writer, a few awkwardly-sized pieces at a time.
+        let mut writer = CipherWriter::new(block.encryptor().unwrap(), Vec::new());
+        for chunk in data.bytes().chunks(1234) {
+            writer.write_all(chunk).unwrap();
+        }
+        let ciphertext = writer.finish().unwrap();
+        assert_eq!(ciphertext, block.encrypt(&data).unwrap().bytes());
+
+        // Decrypt through a reader.
+        let mut reader = CipherReader::new(block.decryptor().unwrap(), &ciphertext[..]);
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext).unwrap();
+        assert_eq!(plaintext, data.bytes());
+    }
+
+    /// A reader which fails with an interruption on its first read, and then reads normally.
+    struct InterruptOnce<R: Read> {
+        inner: R,
+        interrupted: bool,
+    }
+
+    impl<R: Read> Read for InterruptOnce<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.interrupted {
+                self.interrupted = true;
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    #[test]
+    fn reader_interrupted() {
+        let block = aes(OperationModes::Ecb, PaddingSchemes::Pkcs7);
+        let data = Data::random(100);
+        let ciphertext = block.encrypt(&data).unwrap();
+
+        // An interruption is retried by read_to_end, and must not be mistaken for the end of input.
+        let inner = InterruptOnce {
+            inner: ciphertext.bytes(),
+            interrupted: false,
+        };
+        let mut reader = CipherReader::new(block.decryptor().unwrap(), inner);
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext).unwrap();
+        assert_eq!(plaintext, data.bytes());
+    }
+
+    #[test]
+    fn errors() {
+        let block = aes(OperationModes::Ecb, PaddingSchemes::NoPadding);
+        let mut encryptor = block.encryptor().unwrap();
+        encryptor.update(&[0; 17]);
+        assert!(encryptor.finalize().is_err());
+
+        let block = aes(OperationModes::Ecb, PaddingSchemes::Pkcs7);
+        let mut decryptor = block.decryptor().unwrap();
+        decryptor.update(&[0; 17]);
+        assert!(decryptor.finalize().is_err());
+
+        let block = aes(OperationModes::Ofb(Data::new()), PaddingSchemes::Pkcs7);
+        assert!(block.encryptor().is_err());
+        assert!(block.decryptor().is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Encryption and decryption of disk sectors using AES in XTS mode.
+//!
+//! See IEEE 1619 and NIST SP 800-38E.
+
+use utils::block::{Cipher, EncryptError, DecryptError, xor_in_place};
+use utils::block::aes::AesCipher;
+use utils::data::Data;
+
+/// An AES-XTS encryptor and decryptor for sectors of a disk.
+///
+/// Each sector is encrypted independently, with a tweak derived from its index, so identical
+/// data in different sectors encrypts differently. Within a sector, each 16-byte block is
+/// encrypted like ECB mode with a different mask, so there is no authentication: changing one
+/// block of ciphertext scrambles the corresponding block of plaintext and leaves the others
+/// alone.
+pub struct AesXts {
+    /// The AES cipher used to encrypt the data.
+    data_cipher: AesCipher,
+    /// The AES cipher used to encrypt the sector index to form the tweak.
+    tweak_cipher: AesCipher,
+}
+
+impl AesXts {
+    /// Creates a new AesXts which uses the given key, which is the concatenation of the data key
+    /// and the tweak key. The key must be 32 or 64 bytes, for AES-128 or AES-256.
+    pub fn new(key: &Data) -> Result<AesXts, String> {
+        if key.len() != 32 && key.len() != 64 {
+            return Err("Invalid key length - must be 32 or 64 bytes".to_string());
+        }
+
+        let (data_key, tweak_key) = key.bytes().split_at(key.len() / 2);
+        match (AesCipher::new(data_key), AesCipher::new(tweak_key)) {
+            (Ok(data_cipher), Ok(tweak_cipher)) => {
+                Ok(AesXts {
+                    data_cipher,
+                    tweak_cipher,
+                })
+            }
+            (Err(err), _) | (_, Err(err)) => Err(format!("{}", err)),
+        }
+    }
+
+    /// Encrypts the data in the sector with the given index. The data must be at least one block
+    /// long, and if it is not a whole number of blocks then ciphertext stealing is used, so the
+    /// ciphertext is always the same length as the plaintext.
+    pub fn encrypt_sector(&self, sector_index: u128, data: &Data) -> Result<Data, EncryptError> {
+        if data.len() < 16 {
+            return Err(EncryptError::DataLength);
+        }
+        Ok(self.transform(sector_index, data, false))
+    }
+
+    /// Decrypts the data in the sector with the given index.
+    pub fn decrypt_sector(&self, sector_index: u128, data: &Data) -> Result<Data, DecryptError> {
+        if data.len() < 16 {
+            return Err(DecryptError::DataLength);
+        }
+        Ok(self.transform(sector_index, data, true))
+    }
+
+    /// Encrypts or decrypts the data in the sector with the given index, which must be at least
+    /// one block long.
+    fn transform(&self, sector_index: u128, data: &Data, decrypt: bool) -> Data {
+
+        // The initial tweak is the encryption of the sector index, in little-endian order.
+        let mut tweak = sector_index.to_le_bytes();
+        self.tweak_cipher.encrypt_block(&mut tweak);
+
+        // Copy the message into the output, and transform the whole blocks in place, using the
+        // next tweak for each block.
+        let mut output = data.bytes().to_vec();
+        let tail_len = data.len() % 16;
+        let whole_len = data.len() - tail_len;
+        let mut tweaks = Vec::with_capacity(whole_len / 16 + 1);
+        for _ in 0..whole_len / 16 + 1 {
+            tweaks.push(tweak);
+            tweak = mul_alpha(&tweak);
+        }
+
+        // When stealing ciphertext, the last whole block and the partial block are handled
+        // separately below. On decryption, they use their tweaks in the opposite order.
+        let stolen_blocks = if tail_len == 0 { 0 } else { 1 };
+        for (block, tweak) in output[..whole_len - 16 * stolen_blocks]
+            .chunks_mut(16)
+            .zip(tweaks.iter()) {
+            self.transform_block(block, tweak, decrypt);
+        }
+        if tail_len == 0 {
+            return Data::from_bytes(output);
+        }
+
+        // Transform the last whole block, and swap the start of the result with the partial
+        // block. The end of the result, which is now in the partial block, is then transformed
+        // along with it into the last whole block.
+        let last_start = whole_len - 16;
+        let last_index = whole_len / 16 - 1;
+        let (first_tweak, second_tweak) = if decrypt {
+            (&tweaks[last_index + 1], &tweaks[last_index])
+        } else {
+            (&tweaks[last_index], &tweaks[last_index + 1])
+        };
+        let (last, tail) = output[last_start..].split_at_mut(16);
+        self.transform_block(last, first_tweak, decrypt);
+        last[..tail_len].swap_with_slice(tail);
+        self.transform_block(last, second_tweak, decrypt);
+
+        Data::from_bytes(output)
+    }
+
+    /// Encrypts or decrypts a single block in place with the given tweak.
+    fn transform_block(&self, block: &mut [u8], tweak: &[u8; 16], decrypt: bool) {
+        xor_in_place(block, tweak);
+        if decrypt {
+            self.data_cipher.decrypt_block(block);
+        } else {
+            self.data_cipher.encrypt_block(block);
+        }
+        xor_in_place(block, tweak);
+    }
+}
+
+/// Multiplies a tweak by the primitive element alpha of GF(2^128), as defined by IEEE 1619,
+/// where the bytes of the tweak are in little-endian order.
+fn mul_alpha(tweak: &[u8; 16]) -> [u8; 16] {
+    let value = u128::from_le_bytes(*tweak);
+    let reduction = if value >> 127 == 1 { 0x87 } else { 0 };
+    ((value << 1) ^ reduction).to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::xts::AesXts;
+    use utils::data::Data;
+
+    /// The keys used by the IEEE 1619 ciphertext-stealing vectors.
+    const STEALING_KEY: &str = "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0";
+
+    /// Encrypt and decrypt the given sector, and check that the ciphertext starts and ends with
+    /// the given hex strings.
+    fn check_vector(key: &str, sector_index: u128, plaintext: &Data, start: &str, end: &str) {
+        let xts = AesXts::new(&Data::from_hex(key).unwrap()).unwrap();
+        let ciphertext = xts.encrypt_sector(sector_index, plaintext).unwrap();
+        assert!(ciphertext.to_hex().starts_with(start));
+        assert!(ciphertext.to_hex().ends_with(end));
+        let decrypted = xts.decrypt_sector(sector_index, &ciphertext).unwrap();
+        assert_eq!(decrypted.to_hex(), plaintext.to_hex());
+    }
+
+    /// Returns 512 bytes counting up from zero and wrapping around, as used by several vectors.
+    fn counting_sector() -> Data {
+        Data::from_bytes((0..512).map(|x| x as u8).collect())
+    }
+
+    #[test]
+    fn ieee_1619_vectors() {
+        check_vector(&"00".repeat(32),
+                     0,
+                     &Data::from_bytes(vec![0; 32]),
+                     "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e",
+                     "");
+        check_vector(&format!("{}{}", "11".repeat(16), "22".repeat(16)),
+                     0x3333333333,
+                     &Data::from_bytes(vec![0x44; 32]),
+                     "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0",
+                     "");
+        check_vector("27182818284590452353602874713526\
+                      31415926535897932384626433832795",
+                     0,
+                     &counting_sector(),
+                     "27a7479befa1d476489f308cd4cfa6e2a96e4bbe3208ff25287dd3819616e89c",
+                     "eb4a427d1923ce3ff262735779a418f20a282df920147beabe421ee5319d0568");
+        check_vector("27182818284590452353602874713526\
+                      31415926535897932384626433832795",
+                     1,
+                     &counting_sector(),
+                     "bbf9d6a74a7465fee20f42adf9a623fc954f3b55587e8e429eec6f71e738a390",
+                     "");
+        check_vector("2718281828459045235360287471352662497757247093699959574966967627\
+                      3141592653589793238462643383279502884197169399375105820974944592",
+                     0xff,
+                     &counting_sector(),
+                     "1c3b3a102f770386e4836c99e370cf9bea00803f5e482357a4ae12d414a3e63b",
+                     "773dad38014bd2092fa755c824bb5e54c4f36ffda9fcea70b9c6e693e148c151");
+    }
+
+    #[test]
+    fn ciphertext_stealing() {
+        let vectors = [(17, "6c1625db4671522d3d7599601de7ca09ed"),
+                       (18, "d069444b7a7e0cab09e24447d24deb1fedbf"),
+                       (19, "e5df1351c0544ba1350b3363cd8ef4beedbf9d"),
+                       (20, "9d84c813f719aa2c7be3f66171c7c5c2edbf9dac")];
+        for &(len, ciphertext) in &vectors {
+            let plaintext = Data::from_bytes((0..len).collect());
+            check_vector(STEALING_KEY, 0x123456789a, &plaintext, ciphertext, "");
+        }
+
+        let xts = AesXts::new(&Data::from_hex(STEALING_KEY).unwrap()).unwrap();
+        assert!(xts.encrypt_sector(0, &Data::from_bytes(vec![0; 15])).is_err());
+        assert!(xts.decrypt_sector(0, &Data::from_bytes(vec![0; 15])).is_err());
+        assert!(AesXts::new(&Data::from_bytes(vec![0; 16])).is_err());
+    }
+
+    #[test]
+    fn malleability() {
+        let xts = AesXts::new(&Data::random(32)).unwrap();
+        let plaintext = Data::from_text("Pay Alice $100; Pay Bob $200;;;;Pay Carol $300;;;");
+
+        // The same data encrypts differently in different sectors.
+        let ciphertext = xts.encrypt_sector(7, &plaintext).unwrap();
+        assert!(ciphertext.bytes() != xts.encrypt_sector(8, &plaintext).unwrap().bytes());
+
+        // Changing a byte of the ciphertext scrambles only the block containing it.
+        let mut tampered = ciphertext.bytes().to_vec();
+        tampered[20] ^= 1;
+        let decrypted = xts.decrypt_sector(7, &Data::from_bytes(tampered)).unwrap();
+        assert_eq!(&decrypted.bytes()[..16], &plaintext.bytes()[..16]);
+        assert!(decrypted.bytes()[16..32] != plaintext.bytes()[16..32]);
+        assert_eq!(&decrypted.bytes()[32..], &plaintext.bytes()[32..]);
+    }
+}
@@ -0,0 +1,143 @@
+//! Arithmetic in the finite field GF(2^128), as used by GHASH in AES-GCM.
+//!
+//! Elements are polynomials over GF(2) modulo x^128 + x^7 + x^2 + x + 1. They are converted to
+//! and from 16-byte blocks using the bit ordering of the GCM specification, in which the most
+//! significant bit of the first byte is the coefficient of x^0.
+
+use std::fmt;
+use std::ops::{Add, Mul};
+
+use utils::data::Data;
+
+/// The reduction polynomial x^128 + x^7 + x^2 + x + 1, without the x^128 term, in GCM bit order.
+const R: u128 = 0xe1 << 120;
+
+/// An element of the field GF(2^128).
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gf128 {
+    /// The coefficients of the polynomial, with the coefficient of x^0 in the top bit.
+    value: u128,
+}
+
+impl Gf128 {
+    /// Returns the additive identity.
+    pub fn zero() -> Gf128 {
+        Gf128 { value: 0 }
+    }
+
+    /// Returns the multiplicative identity.
+    pub fn one() -> Gf128 {
+        Gf128 { value: 1 << 127 }
+    }
+
+    /// Creates a new field element from a block of at most 16 bytes. Shorter blocks are padded
+    /// on the right with zeroes.
+    pub fn from_bytes(bytes: &[u8]) -> Gf128 {
+        assert!(bytes.len() <= 16, "A field element is at most 16 bytes long");
+        let mut block = [0; 16];
+        block[..bytes.len()].copy_from_slice(bytes);
+        Gf128 { value: u128::from_be_bytes(block) }
+    }
+
+    /// Creates a new field element from a `Data` of at most 16 bytes.
+    pub fn from_data(data: &Data) -> Gf128 {
+        Gf128::from_bytes(data.bytes())
+    }
+
+    /// Returns the 16-byte block representing this field element.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        self.value.to_be_bytes()
+    }
+
+    /// Returns the 16-byte block representing this field element as a `Data`.
+    pub fn to_data(&self) -> Data {
+        Data::from_bytes(self.to_bytes().to_vec())
+    }
+
+    /// Returns true if this is the zero element, and false otherwise.
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    /// Raises this element to the given power.
+    pub fn pow(&self, mut exponent: u128) -> Gf128 {
+        let mut result = Gf128::one();
+        let mut base = *self;
+        while exponent != 0 {
+            if exponent & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// Returns the multiplicative inverse of this element, or `None` if it is zero.
+    pub fn inverse(&self) -> Option<Gf128> {
+        if self.is_zero() {
+            None
+        } else {
+            // The multiplicative group has order 2^128 - 1.
+            Some(self.pow(u128::MAX - 1))
+        }
+    }
+}
+
+impl Add for Gf128 {
+    type Output = Gf128;
+
+    /// Adds two field elements, which is the same as XORing their coefficients.
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, other: Gf128) -> Gf128 {
+        Gf128 { value: self.value ^ other.value }
+    }
+}
+
+impl Mul for Gf128 {
+    type Output = Gf128;
+
+    /// Multiplies two field elements, using Algorithm 1 of NIST SP 800-38D.
+    fn mul(self, other: Gf128) -> Gf128 {
+        let mut result = 0;
+        let mut multiple = other.value;
+        for ix in 0..128 {
+            if (self.value >> (127 - ix)) & 1 == 1 {
+                result ^= multiple;
+            }
+            multiple = if multiple & 1 == 1 { (multiple >> 1) ^ R } else { multiple >> 1 };
+        }
+        Gf128 { value: result }
+    }
+}
+
+impl fmt::Display for Gf128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:032x}", self.value)
+    }
+}
+
+impl fmt::Debug for Gf128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::gf128::Gf128;
+
+    #[test]
+    fn field_identities() {
+        let x = Gf128::from_bytes(&[0x66, 0xe9, 0x4b, 0xd4, 0xef, 0x8a, 0x2c, 0x3b, 0x88, 0x4c,
+                                    0xfa, 0x59, 0xca, 0x34, 0x2b, 0x2e]);
+        let y = Gf128::from_bytes(&[0x03, 0x88, 0xda, 0xce, 0x60, 0xb6, 0xa3, 0x92]);
+        assert_eq!(x * Gf128::one(), x);
+        assert_eq!(x + x, Gf128::zero());
+        assert_eq!(x * y, y * x);
+        assert_eq!(x * x.inverse().unwrap(), Gf128::one());
+        assert_eq!(x.pow(3), x * x * x);
+        assert!(Gf128::zero().inverse().is_none());
+    }
+}
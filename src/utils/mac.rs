@@ -0,0 +1,234 @@
+//! Message authentication codes built on block ciphers.
+
+use utils::block::{Algorithms, BlockCipher, Cipher, EncryptError, OperationModes, PaddingSchemes};
+use utils::data::Data;
+
+/// Trait for computing and checking message authentication codes.
+pub trait Mac {
+    /// Computes the authentication tag for the given message.
+    fn tag(&self, message: &Data) -> Result<Data, EncryptError>;
+
+    /// Checks whether the given tag is valid for the given message. The comparison of the tags
+    /// takes the same time wherever they differ, so it does not leak how much of a forgery is
+    /// correct.
+    fn verify(&self, message: &Data, tag: &Data) -> bool {
+        match self.tag(message) {
+            Ok(expected) => fixed_time_eq(expected.bytes(), tag.bytes()),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Compares two byte sequences for equality in time which depends only on their lengths.
+pub fn fixed_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    left.iter().zip(right.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// CBC-MAC, where the tag is the last block of the CBC encryption of the message.
+///
+/// This is only secure for messages of a single fixed length: given the tag of one message, it is
+/// easy to forge the tag of a longer message.
+pub struct CbcMac {
+    /// The block cipher, in CBC mode with the fixed initialisation vector.
+    block: BlockCipher,
+}
+
+impl CbcMac {
+    /// Returns a new CbcMac using the given algorithm, key and initialisation vector. Messages are
+    /// padded using the given padding scheme before being encrypted.
+    pub fn new(algorithm: Algorithms,
+               key: &Data,
+               iv: &Data,
+               padding: PaddingSchemes)
+               -> Result<CbcMac, String> {
+        let mode = OperationModes::Cbc(iv.clone());
+        Ok(CbcMac { block: BlockCipher::new(algorithm, mode, padding, key)? })
+    }
+}
+
+impl Mac for CbcMac {
+    fn tag(&self, message: &Data) -> Result<Data, EncryptError> {
+        let ciphertext = self.block.encrypt(message)?;
+        if ciphertext.is_empty() {
+            return Err(EncryptError::DataLength);
+        }
+        let block_size = self.block.block_size();
+        Ok(ciphertext.slice(ciphertext.len() - block_size, ciphertext.len()))
+    }
+}
+
+/// CMAC, as specified in NIST SP 800-38B and, for AES, RFC 4493.
+///
+/// This fixes the weakness of CBC-MAC for variable-length messages by XORing one of two subkeys,
+/// derived from the key, into the last block before it is encrypted.
+pub struct Cmac {
+    /// The cipher used to encrypt single blocks.
+    cipher: Box<dyn Cipher>,
+    /// The subkey used when the last block is complete.
+    k1: Vec<u8>,
+    /// The subkey used when the last block has been padded.
+    k2: Vec<u8>,
+}
+
+/// Doubles a block in GF(2^n), where n is its size in bits, using the reduction polynomial for
+/// that block size from SP 800-38B.
+fn double(block: &[u8]) -> Vec<u8> {
+    let reduction = match block.len() {
+        8 => 0x1b,
+        _ => 0x87,
+    };
+    let mut doubled: Vec<u8> = block.iter()
+        .zip(block.iter().skip(1).chain(Some(&0)))
+        .map(|(&byte, &next)| (byte << 1) | (next >> 7))
+        .collect();
+    let last = doubled.len() - 1;
+    doubled[last] ^= reduction & (block[0] >> 7).wrapping_neg();
+    doubled
+}
+
+impl Cmac {
+    /// Returns a new Cmac using the given algorithm and key. Only algorithms with 8-byte or
+    /// 16-byte blocks are supported.
+    pub fn new(algorithm: Algorithms, key: &Data) -> Result<Cmac, String> {
+        Cmac::from_cipher(algorithm.cipher(key)?)
+    }
+
+    /// Returns a new Cmac using the given single-block cipher, which is already keyed. This allows
+    /// any implementation of `Cipher` to be used, not just those listed in `Algorithms`. Only
+    /// ciphers with 8-byte or 16-byte blocks are supported.
+    pub fn from_cipher(cipher: Box<dyn Cipher>) -> Result<Cmac, String> {
+        let block_size = cipher.block_size();
+        if block_size != 8 && block_size != 16 {
+            return Err(format!("CMAC is not defined for a block size of {} bytes", block_size));
+        }
+
+        // Derive the subkeys from the encryption of the all-zero block.
+        let mut zero = vec![0; block_size];
+        cipher.encrypt_block(&mut zero);
+        let k1 = double(&zero);
+        let k2 = double(&k1);
+
+        Ok(Cmac { cipher, k1, k2 })
+    }
+}
+
+impl Mac for Cmac {
+    fn tag(&self, message: &Data) -> Result<Data, EncryptError> {
+        let block_size = self.cipher.block_size();
+        let bytes = message.bytes();
+
+        // Split off the last block, which is padded with a single 1 bit and then 0 bits if it is
+        // empty or incomplete, and XORed with the appropriate subkey.
+        let last_len = match bytes.len() % block_size {
+            0 if !bytes.is_empty() => block_size,
+            len => len,
+        };
+        let (init, last) = bytes.split_at(bytes.len() - last_len);
+        let mut last_block = last.to_vec();
+        let subkey = if last_len == block_size {
+            &self.k1
+        } else {
+            last_block.push(0x80);
+            last_block.resize(block_size, 0);
+            &self.k2
+        };
+        for (x, y) in last_block.iter_mut().zip(subkey.iter()) {
+            *x ^= *y;
+        }
+
+        // Run CBC-MAC over the blocks with a zero initialisation vector.
+        let mut state = vec![0; block_size];
+        for block in init.chunks(block_size).chain(Some(&last_block[..])) {
+            for (x, y) in state.iter_mut().zip(block.iter()) {
+                *x ^= *y;
+            }
+            self.cipher.encrypt_block(&mut state);
+        }
+
+        Ok(Data::from_bytes(state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::{Algorithms, PaddingSchemes};
+    use utils::data::Data;
+    use utils::mac::{CbcMac, Cmac, Mac};
+
+    const KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const MESSAGE: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                           30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    #[test]
+    fn rfc_4493_vectors() {
+        let cmac = Cmac::new(Algorithms::Aes, &Data::from_hex(KEY).unwrap()).unwrap();
+        assert_eq!(Data::from_bytes(cmac.k1.clone()).to_hex(), "fbeed618357133667c85e08f7236a8de");
+        assert_eq!(Data::from_bytes(cmac.k2.clone()).to_hex(), "f7ddac306ae266ccf90bc11ee46d513b");
+
+        let message = Data::from_hex(MESSAGE).unwrap();
+        let vectors = [(0, "bb1d6929e95937287fa37d129b756746"),
+                       (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+                       (40, "dfa66747de9ae63030ca32611497c827"),
+                       (64, "51f0bebf7e3b9d92fc49741779363cfe")];
+        for &(len, tag) in &vectors {
+            let prefix = message.slice(0, len);
+            assert_eq!(cmac.tag(&prefix).unwrap().to_hex(), tag);
+            assert!(cmac.verify(&prefix, &Data::from_hex(tag).unwrap()));
+        }
+        let wrong_tag = Data::from_hex("51f0bebf7e3b9d92fc49741779363cff").unwrap();
+        assert!(!cmac.verify(&message, &wrong_tag));
+        assert!(!cmac.verify(&message, &Data::from_hex("51f0bebf").unwrap()));
+    }
+
+    #[test]
+    fn from_cipher() {
+        // Any keyed cipher can be used, such as the bitsliced AES core.
+        let key = Data::from_hex(KEY).unwrap();
+        let bitsliced = Algorithms::AesConstantTime.cipher(&key).unwrap();
+        let cmac = Cmac::from_cipher(bitsliced).unwrap();
+        let message = Data::from_hex(MESSAGE).unwrap();
+        assert_eq!(cmac.tag(&message).unwrap().to_hex(), "51f0bebf7e3b9d92fc49741779363cfe");
+
+        let null = Algorithms::Null(12).cipher(&Data::new()).unwrap();
+        assert!(Cmac::from_cipher(null).is_err());
+    }
+
+    #[test]
+    fn cbc_mac_length_extension() {
+        let key = Data::from_hex(KEY).unwrap();
+        let iv = Data::from_bytes(vec![0; 16]);
+        let cbc_mac = CbcMac::new(Algorithms::Aes, &key, &iv, PaddingSchemes::NoPadding).unwrap();
+        let cmac = Cmac::new(Algorithms::Aes, &key).unwrap();
+
+        // Given the tag t of a one-block message m, the message m || (m ^ t) also has tag t
+        // under CBC-MAC, since the second block encrypts to E(m ^ t ^ t) = t.
+        let message = Data::from_text("Pay Eve $1000000");
+        let tag = cbc_mac.tag(&message).unwrap();
+        let mut forgery = message.bytes().to_vec();
+        forgery.extend(message.bytes().iter().zip(tag.bytes().iter()).map(|(x, y)| x ^ y));
+        let forgery = Data::from_bytes(forgery);
+        assert!(cbc_mac.verify(&forgery, &tag));
+
+        // The same trick does not work against CMAC.
+        let tag = cmac.tag(&message).unwrap();
+        let mut forgery = message.bytes().to_vec();
+        forgery.extend(message.bytes().iter().zip(tag.bytes().iter()).map(|(x, y)| x ^ y));
+        assert!(!cmac.verify(&Data::from_bytes(forgery), &tag));
+    }
+
+    #[test]
+    fn cbc_mac_padding() {
+        let key = Data::from_hex(KEY).unwrap();
+        let iv = Data::from_bytes(vec![0; 16]);
+        let unpadded = CbcMac::new(Algorithms::Aes, &key, &iv, PaddingSchemes::NoPadding).unwrap();
+        let padded = CbcMac::new(Algorithms::Aes, &key, &iv, PaddingSchemes::Pkcs7).unwrap();
+        let message = Data::from_text("Not a whole block");
+        assert!(unpadded.tag(&message).is_err());
+        assert!(!unpadded.verify(&message, &Data::new()));
+        assert_eq!(padded.tag(&message).unwrap().len(), 16);
+    }
+}
@@ -3,5 +3,8 @@
 
 pub mod block;
 pub mod data;
+pub mod gf128;
+pub mod mac;
 pub mod metrics;
+pub mod padding;
 pub mod xor;
@@ -0,0 +1,347 @@
+//! Padding schemes for extending data to a whole number of blocks, and removing that padding
+//! again after decryption.
+//!
+//! The padding functions here can be used on their own, without needing a `BlockCipher`. The
+//! functions for individual schemes assume a block size of between 1 and 255 bytes, which `pad`
+//! checks.
+
+use rand;
+use rand::Rng;
+
+use utils::block::{PaddingSchemes, EncryptError, DecryptError};
+use utils::data::Data;
+
+/// Pads the given data to a multiple of the given block size using the given padding scheme.
+///
+/// The block size must be between 1 and 255 bytes, so that the length of the padding always fits
+/// in a single byte.
+pub fn pad(data: &Data, block_size: usize, scheme: &PaddingSchemes) -> Result<Data, EncryptError> {
+    if block_size == 0 || block_size > 255 {
+        return Err(EncryptError::DataLength);
+    }
+    match *scheme {
+        PaddingSchemes::NoPadding => {
+            if !data.len().is_multiple_of(block_size) {
+                return Err(EncryptError::DataLength);
+            }
+            Ok(data.clone())
+        }
+        PaddingSchemes::Pkcs7 => Ok(pkcs7_pad(data, block_size)),
+        PaddingSchemes::Zero => Ok(zero_pad(data, block_size)),
+        PaddingSchemes::AnsiX923 => Ok(ansi_x923_pad(data, block_size)),
+        PaddingSchemes::Iso10126 => Ok(iso10126_pad(data, block_size)),
+        PaddingSchemes::Iso7816 => Ok(iso7816_pad(data, block_size)),
+    }
+}
+
+/// Removes padding from the given data, which was padded to the given block size using the given
+/// padding scheme.
+pub fn unpad(data: &Data,
+             block_size: usize,
+             scheme: &PaddingSchemes)
+             -> Result<Data, DecryptError> {
+    match *scheme {
+        PaddingSchemes::NoPadding => Ok(data.clone()),
+        PaddingSchemes::Pkcs7 => pkcs7_unpad(data, block_size),
+        PaddingSchemes::Zero => zero_unpad(data, block_size),
+        PaddingSchemes::AnsiX923 => ansi_x923_unpad(data, block_size),
+        PaddingSchemes::Iso10126 => iso10126_unpad(data, block_size),
+        PaddingSchemes::Iso7816 => iso7816_unpad(data, block_size),
+    }
+}
+
+/// Returns the number of padding bytes needed to extend data of the given length to a multiple of
+/// the given block size, always adding at least one byte.
+fn pad_len(len: usize, block_size: usize) -> usize {
+    block_size - len % block_size
+}
+
+/// Returns the given data, followed by the given padding bytes.
+fn append(data: &Data, padding: &[u8]) -> Data {
+    let mut new_bytes = Vec::with_capacity(data.len() + padding.len());
+    new_bytes.extend_from_slice(data.bytes());
+    new_bytes.extend_from_slice(padding);
+    Data::from_bytes(new_bytes)
+}
+
+/// Reads the padding length from the final byte of the given data, and checks that it is between
+/// 1 and the block size, and no longer than the data itself.
+fn read_pad_len(data: &Data, block_size: usize) -> Result<usize, DecryptError> {
+    let pad = match data.bytes().last() {
+        Some(&byte) => byte as usize,
+        None => return Err(DecryptError::Padding),
+    };
+    if pad == 0 || pad > block_size || pad > data.len() {
+        return Err(DecryptError::Padding);
+    }
+    Ok(pad)
+}
+
+/// Pads the given data using PKCS#7: N bytes, each of value N.
+pub fn pkcs7_pad(data: &Data, block_size: usize) -> Data {
+    let pad = pad_len(data.len(), block_size);
+    append(data, &vec![pad as u8; pad])
+}
+
+/// Removes PKCS#7 padding from the given data.
+///
+/// The padding is checked in constant time: the same sequence of operations is performed for
+/// every input of a given length, regardless of the value of the padding or where it is wrong.
+/// Only the length of the data and the final valid/invalid outcome are revealed. A pad byte of
+/// zero, or one greater than the block size or the length of the data, is rejected.
+pub fn pkcs7_unpad(data: &Data, block_size: usize) -> Result<Data, DecryptError> {
+
+    // The length of the data is public, so it is fine to reject empty data straight away.
+    let bytes = data.bytes();
+    let len = bytes.len();
+    if len == 0 {
+        return Err(DecryptError::Padding);
+    }
+
+    // Only the last block can contain padding, so examine exactly that many bytes, whatever the
+    // value of the pad byte.
+    let pad = bytes[len - 1] as u32;
+    let window = if len < block_size { len } else { block_size };
+
+    // Accumulate a mask which is non-zero if anything is wrong with the padding, without
+    // branching on any of the data.
+    let mut bad = ct_eq_mask(pad, 0) | !ct_lt_mask(pad, window as u32 + 1);
+    for (ix, &byte) in bytes[len - window..].iter().rev().enumerate() {
+        let in_padding = ct_lt_mask(ix as u32, pad);
+        bad |= in_padding & !ct_eq_mask(byte as u32, pad);
+    }
+
+    if bad != 0 {
+        return Err(DecryptError::Padding);
+    }
+    Ok(data.slice(0, len - pad as usize))
+}
+
+/// Returns a mask of all ones if `x < y`, and zero otherwise, without branching. Both inputs must
+/// be less than 2^31.
+fn ct_lt_mask(x: u32, y: u32) -> u32 {
+    0u32.wrapping_sub(x.wrapping_sub(y) >> 31)
+}
+
+/// Returns a mask of all ones if `x == y`, and zero otherwise, without branching.
+fn ct_eq_mask(x: u32, y: u32) -> u32 {
+    let diff = x ^ y;
+    0u32.wrapping_sub(((diff | diff.wrapping_neg()) >> 31) ^ 1)
+}
+
+/// Pads the given data with zero bytes up to a multiple of the block size. No padding is added if
+/// the data is already a multiple of the block size.
+pub fn zero_pad(data: &Data, block_size: usize) -> Data {
+    let pad = pad_len(data.len(), block_size) % block_size;
+    append(data, &vec![0; pad])
+}
+
+/// Removes zero padding from the given data, by stripping up to one less than a block of trailing
+/// zero bytes. This never fails.
+///
+/// Note that zero padding is ambiguous: any zero bytes at the end of the original data are lost.
+pub fn zero_unpad(data: &Data, block_size: usize) -> Result<Data, DecryptError> {
+    let pad = data.bytes().iter().rev().take(block_size - 1).take_while(|&&x| x == 0).count();
+
+    Ok(data.slice(0, data.len() - pad))
+}
+
+/// Pads the given data using ANSI X.923: N - 1 zero bytes followed by a single byte of value N.
+pub fn ansi_x923_pad(data: &Data, block_size: usize) -> Data {
+    let pad = pad_len(data.len(), block_size);
+    let mut padding = vec![0; pad];
+    padding[pad - 1] = pad as u8;
+    append(data, &padding)
+}
+
+/// Removes ANSI X.923 padding from the given data.
+pub fn ansi_x923_unpad(data: &Data, block_size: usize) -> Result<Data, DecryptError> {
+
+    // Remove the last N bytes, where N is the value of the final byte, after first checking
+    // that the bytes before the final one are all zero.
+    let pad = read_pad_len(data, block_size)?;
+    let start = data.len() - pad;
+    if data.bytes()[start..data.len() - 1].iter().any(|&x| x != 0) {
+        return Err(DecryptError::Padding);
+    }
+
+    Ok(data.slice(0, start))
+}
+
+/// Pads the given data using ISO 10126: N - 1 random bytes followed by a single byte of value N.
+pub fn iso10126_pad(data: &Data, block_size: usize) -> Data {
+    let pad = pad_len(data.len(), block_size);
+    let mut padding = Vec::with_capacity(pad);
+    let mut rng = rand::thread_rng();
+    for _ in 0..pad - 1 {
+        padding.push(rng.gen::<u8>());
+    }
+    padding.push(pad as u8);
+    append(data, &padding)
+}
+
+/// Removes ISO 10126 padding from the given data. The random bytes cannot be checked, so only
+/// the final length byte is validated.
+pub fn iso10126_unpad(data: &Data, block_size: usize) -> Result<Data, DecryptError> {
+    let pad = read_pad_len(data, block_size)?;
+    Ok(data.slice(0, data.len() - pad))
+}
+
+/// Pads the given data using ISO/IEC 7816-4: a single byte 0x80 followed by zero bytes.
+pub fn iso7816_pad(data: &Data, block_size: usize) -> Data {
+    let pad = pad_len(data.len(), block_size);
+    let mut padding = vec![0; pad];
+    padding[0] = 0x80;
+    append(data, &padding)
+}
+
+/// Removes ISO/IEC 7816-4 padding from the given data.
+pub fn iso7816_unpad(data: &Data, block_size: usize) -> Result<Data, DecryptError> {
+
+    // Skip over the trailing zero bytes, which may not take up a whole block, and check that the
+    // next byte is 0x80.
+    let zeros = data.bytes().iter().rev().take(block_size).take_while(|&&x| x == 0).count();
+    if zeros >= block_size || zeros == data.len() || data.bytes()[data.len() - zeros - 1] != 0x80 {
+        return Err(DecryptError::Padding);
+    }
+
+    Ok(data.slice(0, data.len() - zeros - 1))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::PaddingSchemes;
+    use utils::data::Data;
+    use utils::padding::{pad, unpad};
+
+    /// Check that the given padding scheme round-trips data of every length up to two blocks.
+    fn check_round_trip(scheme: PaddingSchemes) {
+        for len in 0..32 {
+            let data = Data::from_bytes((1..len + 1).collect());
+            let padded = pad(&data, 8, &scheme).unwrap();
+            assert_eq!(padded.len() % 8, 0);
+            assert_eq!(unpad(&padded, 8, &scheme).unwrap().bytes(), data.bytes());
+        }
+    }
+
+    /// Check that the given padded hex string is rejected by the given padding scheme.
+    fn check_invalid(scheme: PaddingSchemes, hex: &str) {
+        assert!(unpad(&Data::from_hex(hex).unwrap(), 8, &scheme).is_err());
+    }
+
+    #[test]
+    fn no_padding() {
+        let aligned = Data::from_hex("0102030405060708").unwrap();
+        assert_eq!(pad(&aligned, 8, &PaddingSchemes::NoPadding).unwrap().bytes(),
+                   aligned.bytes());
+        assert!(pad(&aligned.slice(0, 7), 8, &PaddingSchemes::NoPadding).is_err());
+    }
+
+    #[test]
+    fn invalid_block_sizes() {
+        let data = Data::from_hex("01020304").unwrap();
+        assert!(pad(&data, 0, &PaddingSchemes::Pkcs7).is_err());
+        assert!(pad(&data, 256, &PaddingSchemes::Pkcs7).is_err());
+        assert!(pad(&data, 0, &PaddingSchemes::NoPadding).is_err());
+        assert_eq!(pad(&data, 255, &PaddingSchemes::Pkcs7).unwrap().len(), 255);
+    }
+
+    #[test]
+    fn pkcs7() {
+        check_round_trip(PaddingSchemes::Pkcs7);
+        let data = Data::from_hex("01020304").unwrap();
+        assert_eq!(pad(&data, 8, &PaddingSchemes::Pkcs7).unwrap().to_hex(), "0102030404040404");
+        check_invalid(PaddingSchemes::Pkcs7, "0102030405040404");
+        check_invalid(PaddingSchemes::Pkcs7, "0909090909090909");
+    }
+
+    #[test]
+    fn pkcs7_edge_cases() {
+        let valid = |hex: &str, block_size: usize| {
+            super::pkcs7_unpad(&Data::from_hex(hex).unwrap(), block_size).map(|x| x.to_hex())
+        };
+
+        // Valid paddings, including a whole block of padding and data shorter than a block.
+        assert_eq!(valid("01", 8).unwrap(), "");
+        assert_eq!(valid("0808080808080808", 8).unwrap(), "");
+        assert_eq!(valid("aabbcc0303030303", 8).unwrap(), "aabbcc0303");
+        assert_eq!(valid("aa0202", 8).unwrap(), "aa");
+        assert_eq!(valid("1010101010101010101010101010101010", 16).unwrap(), "10");
+
+        // Empty data.
+        assert!(valid("", 8).is_err());
+
+        // A pad byte of zero.
+        assert!(valid("0102030405060700", 8).is_err());
+        assert!(valid("00", 8).is_err());
+
+        // A pad byte larger than the block size, even when the bytes all match.
+        assert!(valid("090909090909090909", 8).is_err());
+
+        // A pad byte larger than the data.
+        assert!(valid("0303", 8).is_err());
+
+        // A mismatch at either end, or in the middle, of the padding.
+        assert!(valid("aabbcc0503040404", 8).is_err());
+        assert!(valid("aabbcc0404040304", 8).is_err());
+        assert!(valid("aabbcc0404030404", 8).is_err());
+    }
+
+    #[test]
+    fn constant_time_masks() {
+        for x in 0..300 {
+            for y in 0..300 {
+                assert_eq!(super::ct_lt_mask(x, y) == !0, x < y);
+                assert_eq!(super::ct_lt_mask(x, y) == 0, x >= y);
+                assert_eq!(super::ct_eq_mask(x, y) == !0, x == y);
+                assert_eq!(super::ct_eq_mask(x, y) == 0, x != y);
+            }
+        }
+    }
+
+    #[test]
+    fn zero() {
+        let data = Data::from_hex("01020304").unwrap();
+        assert_eq!(pad(&data, 8, &PaddingSchemes::Zero).unwrap().to_hex(), "0102030400000000");
+        let aligned = Data::from_hex("0102030405060708").unwrap();
+        assert_eq!(pad(&aligned, 8, &PaddingSchemes::Zero).unwrap().to_hex(),
+                   "0102030405060708");
+        let padded = Data::from_hex("0102030400000000").unwrap();
+        assert_eq!(unpad(&padded, 8, &PaddingSchemes::Zero).unwrap().to_hex(), "01020304");
+    }
+
+    #[test]
+    fn ansi_x923() {
+        check_round_trip(PaddingSchemes::AnsiX923);
+        let data = Data::from_hex("01020304").unwrap();
+        assert_eq!(pad(&data, 8, &PaddingSchemes::AnsiX923).unwrap().to_hex(),
+                   "0102030400000004");
+        check_invalid(PaddingSchemes::AnsiX923, "0102030400010004");
+        check_invalid(PaddingSchemes::AnsiX923, "0102030405060700");
+        check_invalid(PaddingSchemes::AnsiX923, "0000000000000009");
+    }
+
+    #[test]
+    fn iso10126() {
+        check_round_trip(PaddingSchemes::Iso10126);
+        let data = Data::from_hex("01020304").unwrap();
+        let padded = pad(&data, 8, &PaddingSchemes::Iso10126).unwrap();
+        assert_eq!(padded.slice(0, 4).to_hex(), "01020304");
+        assert_eq!(padded.bytes()[7], 4);
+        check_invalid(PaddingSchemes::Iso10126, "0102030405060700");
+        check_invalid(PaddingSchemes::Iso10126, "0102030405060709");
+    }
+
+    #[test]
+    fn iso7816() {
+        check_round_trip(PaddingSchemes::Iso7816);
+        let data = Data::from_hex("01020304").unwrap();
+        assert_eq!(pad(&data, 8, &PaddingSchemes::Iso7816).unwrap().to_hex(),
+                   "0102030480000000");
+        check_invalid(PaddingSchemes::Iso7816, "0102030400000000");
+        check_invalid(PaddingSchemes::Iso7816, "0102030405060708");
+        check_invalid(PaddingSchemes::Iso7816, "800000000000000000");
+        check_invalid(PaddingSchemes::Iso7816, "");
+    }
+}
@@ -337,4 +337,87 @@ impl CbcCookie {
 
         false
     }
-}
\ No newline at end of file
+}
+
+/// The secret strings, encoded in base 64, one of which is encrypted by a `CbcPaddingOracle`.
+const PADDING_ORACLE_SECRETS: [&str; 10] =
+    ["MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc=",
+     "MDAwMDAxV2l0aCB0aGUgYmFzcyBraWNrZWQgaW4gYW5kIHRoZSBWZWdhJ3MgYXJlIHB1bXBpbic=",
+     "MDAwMDAyUXVpY2sgdG8gdGhlIHBvaW50LCB0byB0aGUgcG9pbnQsIG5vIGZha2luZw==",
+     "MDAwMDAzQ29va2luZyBNQydzIGxpa2UgYSBwb3VuZCBvZiBiYWNvbg==",
+     "MDAwMDA0QnVybmluZyAnZW0sIGlmIHlvdSBhaW4ndCBxdWljayBhbmQgbmltYmxl",
+     "MDAwMDA1SSBnbyBjcmF6eSB3aGVuIEkgaGVhciBhIGN5bWJhbA==",
+     "MDAwMDA2QW5kIGEgaGlnaCBoYXQgd2l0aCBhIHNvdXBlZCB1cCB0ZW1wbw==",
+     "MDAwMDA3SSdtIG9uIGEgcm9sbCwgaXQncyB0aW1lIHRvIGdvIHNvbGFy",
+     "MDAwMDA4b2xsaW4nIGluIG15IGZpdmUgcG9pbnQgb2g=",
+     "MDAwMDA5aXRoIG15IHJhZy10b3AgZG93biBzbyBteSBoYWlyIGNhbiBibG93"];
+
+/// Encrypts a secret string under CBC, and reveals whether ciphertexts have valid padding.
+///
+/// A black box which chooses one of ten secret strings at random, and encrypts it under CBC mode
+/// with PKCS#7 padding, using a fixed, unknown key and a fresh random IV each time. It will also
+/// decrypt any ciphertext and IV, but only reveals whether or not the result was correctly padded.
+///
+/// # Goal
+///
+/// To determine the secret string.
+pub struct CbcPaddingOracle {
+    /// The key used to encrypt and decrypt data.
+    key: Data,
+    /// The secret string which is encrypted.
+    secret: Data,
+}
+
+impl CbcPaddingOracle {
+    /// Creates a new CbcPaddingOracle with a random key and a random choice of secret string.
+    pub fn new() -> CbcPaddingOracle {
+        let choice = rand::thread_rng().gen_range(0, PADDING_ORACLE_SECRETS.len());
+        CbcPaddingOracle {
+            key: Data::random(16),
+            secret: Data::from_base64(PADDING_ORACLE_SECRETS[choice]).unwrap(),
+        }
+    }
+
+    /// Returns a BlockCipher which uses CBC mode with the given IV.
+    fn block(&self, iv: &Data) -> BlockCipher {
+        BlockCipher::new(Algorithms::Aes,
+                         OperationModes::Cbc(iv.clone()),
+                         PaddingSchemes::Pkcs7,
+                         &self.key)
+            .unwrap()
+    }
+
+    /// Encrypts the secret string under a random IV, returning the ciphertext and the IV.
+    pub fn encrypt(&self) -> (Data, Data) {
+        let iv = Data::random(16);
+        (self.block(&iv).encrypt(&self.secret).unwrap(), iv)
+    }
+
+    /// Decrypts the given ciphertext using the given IV, and returns whether the plaintext has
+    /// valid padding.
+    pub fn padding_valid(&self, ciphertext: &Data, iv: &Data) -> bool {
+        iv.len() == 16 && self.block(iv).decrypt(ciphertext).is_ok()
+    }
+
+    /// Checks if the secret string has been correctly determined.
+    pub fn check_answer(&self, secret_guess: &Data) -> bool {
+        secret_guess.bytes() == self.secret.bytes()
+    }
+
+    /// Checks if the given ciphertext and IV have been forged to decrypt to the given message.
+    pub fn check_forgery(&self, ciphertext: &Data, iv: &Data, message: &Data) -> bool {
+        if iv.len() != 16 {
+            return false;
+        }
+        match self.block(iv).decrypt(ciphertext) {
+            Ok(plaintext) => plaintext.bytes() == message.bytes(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Default for CbcPaddingOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,269 @@
+//! Implementations of insecure uses of CBC-MAC.
+
+use rand;
+use rand::Rng;
+
+use utils::block::{Algorithms, PaddingSchemes};
+use utils::data::Data;
+use utils::mac::{CbcMac, Mac};
+
+/// The amount that the attacker wants to steal from the victim.
+pub const TARGET_AMOUNT: u64 = 1_000_000;
+
+/// Returns a random four-digit account number.
+fn random_account() -> u32 {
+    rand::thread_rng().gen_range(1000, 10000)
+}
+
+/// Splits the given bytes into the part before the given prefix, checking that it is present.
+fn strip_prefix<'a>(bytes: &'a [u8], prefix: &str) -> Option<&'a [u8]> {
+    if bytes.starts_with(prefix.as_bytes()) {
+        Some(&bytes[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Parses a decimal number from the given bytes.
+fn parse_number<T: ::std::str::FromStr>(bytes: &[u8]) -> Option<T> {
+    ::std::str::from_utf8(bytes).ok().and_then(|text| text.parse().ok())
+}
+
+/// A bank transfer API authenticated by CBC-MAC with an attacker-controlled IV.
+///
+/// A black box modelling a web client and an API server which share a secret key. The client
+/// will sign transfer requests from the attacker's own account, of the form
+/// `from=<account>&to=<account>&amount=<amount>`, and sends them to the server as the message
+/// followed by a random IV and the CBC-MAC of the message under that IV. The server accepts any
+/// request whose MAC is valid.
+///
+/// # Goal
+///
+/// To create a request which the server accepts as a transfer of 1M spacebucks from the victim's
+/// account to the attacker's account.
+pub struct CbcMacTransfers {
+    /// The key shared between the client and the server.
+    key: Data,
+    /// The attacker's account number.
+    attacker: u32,
+    /// The victim's account number.
+    victim: u32,
+}
+
+impl CbcMacTransfers {
+    /// Creates a new CbcMacTransfers with a random key and random accounts for the attacker and
+    /// the victim.
+    pub fn new() -> CbcMacTransfers {
+        let attacker = random_account();
+        let mut victim = random_account();
+        while victim == attacker {
+            victim = random_account();
+        }
+        CbcMacTransfers {
+            key: Data::random(16),
+            attacker,
+            victim,
+        }
+    }
+
+    /// Returns the attacker's account number.
+    pub fn attacker_account(&self) -> u32 {
+        self.attacker
+    }
+
+    /// Returns the victim's account number.
+    pub fn victim_account(&self) -> u32 {
+        self.victim
+    }
+
+    /// Returns the CBC-MAC of a message under the given IV.
+    fn mac(&self, message: &Data, iv: &Data) -> Data {
+        CbcMac::new(Algorithms::Aes, &self.key, iv, PaddingSchemes::Pkcs7)
+            .unwrap()
+            .tag(message)
+            .unwrap()
+    }
+
+    /// Signs a request for a transfer of the given amount from the attacker's account to the
+    /// given account, returning the message, the IV and the MAC concatenated together.
+    pub fn sign_transfer(&self, to: u32, amount: u64) -> Data {
+        let message = Data::from_text(&format!("from={}&to={}&amount={}",
+                                               self.attacker,
+                                               to,
+                                               amount));
+        let iv = Data::random(16);
+        let mac = self.mac(&message, &iv);
+
+        let mut request = message.bytes().to_vec();
+        request.extend_from_slice(iv.bytes());
+        request.extend_from_slice(mac.bytes());
+        Data::from_bytes(request)
+    }
+
+    /// Verifies a request as the server, returning the transfer it contains as the source
+    /// account, the destination account and the amount, if the MAC is valid.
+    fn process_request(&self, request: &Data) -> Option<(u32, u32, u64)> {
+        if request.len() < 32 {
+            return None;
+        }
+        let message = request.slice(0, request.len() - 32);
+        let iv = request.slice(request.len() - 32, request.len() - 16);
+        let mac = request.slice(request.len() - 16, request.len());
+        if !CbcMac::new(Algorithms::Aes, &self.key, &iv, PaddingSchemes::Pkcs7)
+            .unwrap()
+            .verify(&message, &mac) {
+            return None;
+        }
+
+        let mut fields = message.bytes().split(|&byte| byte == b'&');
+        let from = parse_number(strip_prefix(fields.next()?, "from=")?)?;
+        let to = parse_number(strip_prefix(fields.next()?, "to=")?)?;
+        let amount = parse_number(strip_prefix(fields.next()?, "amount=")?)?;
+        Some((from, to, amount))
+    }
+
+    /// Checks if the server accepts the given request as the theft of 1M spacebucks from the
+    /// victim.
+    pub fn check_answer(&self, request: &Data) -> bool {
+        match self.process_request(request) {
+            Some((from, to, amount)) => {
+                from == self.victim && to == self.attacker && amount >= TARGET_AMOUNT
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for CbcMacTransfers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A bank transfer API authenticated by CBC-MAC with a fixed IV, supporting several transfers in
+/// one request.
+///
+/// A black box modelling a web client and an API server which share a secret key, and use a fixed
+/// IV of zero. Requests are of the form `from=<account>&tx_list=<to>:<amount>(;<to>:<amount>)*`
+/// followed by the CBC-MAC of the message, and the server skips any transactions in the list that
+/// it cannot parse. The client will sign requests from the attacker's own account, and the
+/// attacker has also captured a genuine request from the victim's account.
+///
+/// # Goal
+///
+/// To create a request which the server accepts as including a transfer of 1M spacebucks from the
+/// victim's account to the attacker's account.
+pub struct CbcMacTransferLists {
+    /// The CBC-MAC shared between the client and the server.
+    mac: CbcMac,
+    /// The attacker's account number.
+    attacker: u32,
+    /// The victim's account number.
+    victim: u32,
+}
+
+impl CbcMacTransferLists {
+    /// Creates a new CbcMacTransferLists with a random key and random accounts for the attacker
+    /// and the victim.
+    pub fn new() -> CbcMacTransferLists {
+        let attacker = random_account();
+        let mut victim = random_account();
+        while victim == attacker {
+            victim = random_account();
+        }
+        let mac = CbcMac::new(Algorithms::Aes,
+                              &Data::random(16),
+                              &Data::from_bytes(vec![0; 16]),
+                              PaddingSchemes::Pkcs7)
+            .unwrap();
+        CbcMacTransferLists {
+            mac,
+            attacker,
+            victim,
+        }
+    }
+
+    /// Returns the attacker's account number.
+    pub fn attacker_account(&self) -> u32 {
+        self.attacker
+    }
+
+    /// Returns the victim's account number.
+    pub fn victim_account(&self) -> u32 {
+        self.victim
+    }
+
+    /// Signs a request for the given list of transfers, given as pairs of the destination account
+    /// and the amount, from the given account, returning the message and the MAC concatenated
+    /// together.
+    fn sign(&self, from: u32, transfers: &[(u32, u64)]) -> Data {
+        let tx_list: Vec<String> = transfers.iter()
+            .map(|&(to, amount)| format!("{}:{}", to, amount))
+            .collect();
+        let message = Data::from_text(&format!("from={}&tx_list={}", from, tx_list.join(";")));
+        let mac = self.mac.tag(&message).unwrap();
+
+        let mut request = message.bytes().to_vec();
+        request.extend_from_slice(mac.bytes());
+        Data::from_bytes(request)
+    }
+
+    /// Signs a request for the given list of transfers from the attacker's account, given as
+    /// pairs of the destination account and the amount.
+    pub fn sign_transfers(&self, transfers: &[(u32, u64)]) -> Data {
+        self.sign(self.attacker, transfers)
+    }
+
+    /// Returns a genuine request, signed by the client, for some transfers from the victim's
+    /// account to other accounts.
+    pub fn captured_request(&self) -> Data {
+        let mut rng = rand::thread_rng();
+        let transfers: Vec<(u32, u64)> = (0..rng.gen_range(1, 4))
+            .map(|_| (random_account(), rng.gen_range(1, 1000)))
+            .collect();
+        self.sign(self.victim, &transfers)
+    }
+
+    /// Verifies a request as the server, returning the source account and the list of transfers
+    /// it contains, if the MAC is valid.
+    fn process_request(&self, request: &Data) -> Option<(u32, Vec<(u32, u64)>)> {
+        if request.len() < 16 {
+            return None;
+        }
+        let message = request.slice(0, request.len() - 16);
+        let mac = request.slice(request.len() - 16, request.len());
+        if !self.mac.verify(&message, &mac) {
+            return None;
+        }
+
+        let rest = strip_prefix(message.bytes(), "from=")?;
+        let separator = rest.iter().position(|&byte| byte == b'&')?;
+        let from = parse_number(&rest[..separator])?;
+        let tx_list = strip_prefix(&rest[separator + 1..], "tx_list=")?;
+        let transfers = tx_list.split(|&byte| byte == b';')
+            .filter_map(|transfer| {
+                let colon = transfer.iter().position(|&byte| byte == b':')?;
+                Some((parse_number(&transfer[..colon])?, parse_number(&transfer[colon + 1..])?))
+            })
+            .collect();
+        Some((from, transfers))
+    }
+
+    /// Checks if the server accepts the given request as including the theft of 1M spacebucks
+    /// from the victim.
+    pub fn check_answer(&self, request: &Data) -> bool {
+        match self.process_request(request) {
+            Some((from, transfers)) => {
+                from == self.victim &&
+                transfers.iter().any(|&(to, amount)| to == self.attacker && amount >= TARGET_AMOUNT)
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for CbcMacTransferLists {
+    fn default() -> Self {
+        Self::new()
+    }
+}
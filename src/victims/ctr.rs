@@ -0,0 +1,77 @@
+//! Implementations of insecure uses of CTR mode.
+
+use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes, CounterFormats,
+                   EncryptError};
+use utils::data::Data;
+
+/// Allows editing of a CTR-encrypted message.
+///
+/// A black box which encrypts a secret plaintext under CTR mode with an unknown key and nonce,
+/// and exposes a function which allows the ciphertext to be edited by replacing part of the
+/// underlying plaintext, without revealing the key.
+///
+/// # Goal
+///
+/// To be able to determine the secret plaintext.
+pub struct RandomAccessCtr {
+    /// The BlockCipher used to encrypt and edit data.
+    block: BlockCipher,
+    /// The secret plaintext.
+    plaintext: Data,
+}
+
+impl RandomAccessCtr {
+    /// Creates a new RandomAccessCtr which encrypts the given plaintext.
+    pub fn new(plaintext: Data) -> RandomAccessCtr {
+        let key = Data::random(16);
+        let mode = OperationModes::Ctr {
+            nonce: Data::random(8),
+            counter_format: CounterFormats::LittleEndian64,
+        };
+        let block = BlockCipher::new(Algorithms::Aes, mode, PaddingSchemes::Pkcs7, &key).unwrap();
+        RandomAccessCtr { block, plaintext }
+    }
+
+    /// Returns the encrypted secret plaintext.
+    pub fn ciphertext(&self) -> Data {
+        self.block.encrypt(&self.plaintext).unwrap()
+    }
+
+    /// Edits the given ciphertext, replacing the plaintext starting at the given offset with the
+    /// new plaintext, and returns the new ciphertext. The edit must not start after the end of
+    /// the ciphertext.
+    pub fn edit(&self,
+                ciphertext: &Data,
+                offset: usize,
+                new_plaintext: &Data)
+                -> Result<Data, EncryptError> {
+        self.block.edit(ciphertext, offset, new_plaintext)
+    }
+
+    /// Checks if the plaintext has been correctly determined.
+    pub fn check_answer(&self, plaintext_guess: &Data) -> bool {
+        plaintext_guess.bytes() == self.plaintext.bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use utils::block::EncryptError;
+    use utils::data::Data;
+    use victims::ctr::RandomAccessCtr;
+
+    #[test]
+    fn edit_out_of_range() {
+        let ctr_box = RandomAccessCtr::new(Data::from_text("Attack at dawn"));
+        let ciphertext = ctr_box.ciphertext();
+
+        // An edit may start at the very end of the ciphertext, extending it, but not after it.
+        let edited = ctr_box.edit(&ciphertext, ciphertext.len(), &Data::from_text("!")).unwrap();
+        assert_eq!(edited.len(), ciphertext.len() + 1);
+        match ctr_box.edit(&ciphertext, ciphertext.len() + 1, &Data::from_text("!")) {
+            Err(EncryptError::DataLength) => {}
+            _ => panic!("Expected a data length error"),
+        }
+    }
+}
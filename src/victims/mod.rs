@@ -2,4 +2,7 @@
 //!
 //! These black boxes define a specific goal that an attacker should attempt to achieve.
 
-pub mod block;
\ No newline at end of file
+pub mod block;
+pub mod cbc_mac;
+pub mod ctr;
+pub mod timing;
\ No newline at end of file
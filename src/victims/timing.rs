@@ -0,0 +1,160 @@
+//! Implementations of network services which leak secrets through the time they take to respond.
+//!
+//! The services listen on the loopback interface only, on a port chosen by the operating system.
+
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use utils::block::{BlockCipher, Algorithms, OperationModes, PaddingSchemes};
+use utils::data::Data;
+use utils::mac::{Cmac, Mac};
+
+/// The time taken by the server to check the MAC of a correctly padded token.
+const MAC_CHECK_DELAY: Duration = Duration::from_millis(2);
+
+/// The state shared between the threads of a `TimingPaddingServer`.
+struct ServerState {
+    /// The key used to encrypt and decrypt tokens.
+    key: Data,
+    /// The MAC used to authenticate the contents of tokens.
+    mac: Cmac,
+    /// Whether the server has been asked to stop.
+    stopping: AtomicBool,
+}
+
+impl ServerState {
+    /// Checks a token given as a ciphertext and an IV, returning whether it is valid.
+    fn check_token(&self, ciphertext: &Data, iv: &Data) -> bool {
+        let block = match BlockCipher::new(Algorithms::Aes,
+                                           OperationModes::Cbc(iv.clone()),
+                                           PaddingSchemes::Pkcs7,
+                                           &self.key) {
+            Ok(block) => block,
+            Err(_) => return false,
+        };
+        let plaintext = match block.decrypt(ciphertext) {
+            Ok(plaintext) => plaintext,
+            Err(_) => return false,
+        };
+
+        // Only correctly padded tokens reach the MAC check, which is slow - it stands in for work
+        // such as looking up the user's key in a database before checking the tag.
+        let valid = plaintext.len() >= 16 && {
+            let message = plaintext.slice(0, plaintext.len() - 16);
+            let tag = plaintext.slice(plaintext.len() - 16, plaintext.len());
+            self.mac.verify(&message, &tag)
+        };
+        thread::sleep(MAC_CHECK_DELAY);
+        valid
+    }
+
+    /// Handles a connection from a client, answering each request until the client disconnects.
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let valid = match Data::from_hex(line?.trim()) {
+                Ok(ref request) if request.len() >= 16 => {
+                    self.check_token(&request.slice(16, request.len()), &request.slice(0, 16))
+                }
+                _ => false,
+            };
+            writer.write_all(if valid { b"OK\n" } else { b"ERROR\n" })?;
+        }
+        Ok(())
+    }
+}
+
+/// A token-checking service on a local TCP port, which leaks padding validity through its timing.
+///
+/// A black box which issues an encrypted token, containing a secret session identifier, and runs
+/// a server which checks tokens. Each line sent to the server should be the hex encoding of an IV
+/// followed by a ciphertext. The server decrypts this under CBC mode with PKCS#7 padding, and
+/// checks that the plaintext ends with a valid CMAC of the rest of it. It replies `OK` if the
+/// token is valid, and the same `ERROR` whether the padding or the MAC was wrong - but only
+/// correctly padded tokens reach the slow MAC check.
+///
+/// # Goal
+///
+/// To determine the contents of the token, by connecting to the server.
+pub struct TimingPaddingServer {
+    /// The state shared with the server threads.
+    state: Arc<ServerState>,
+    /// The address that the server is listening on.
+    address: SocketAddr,
+    /// The secret message contained in the token.
+    message: Data,
+}
+
+impl TimingPaddingServer {
+    /// Creates a new TimingPaddingServer with random keys and a random session identifier, and
+    /// starts it listening on a free port on the loopback interface.
+    pub fn start() -> io::Result<TimingPaddingServer> {
+        let state = Arc::new(ServerState {
+            key: Data::random(16),
+            mac: Cmac::new(Algorithms::Aes, &Data::random(16)).unwrap(),
+            stopping: AtomicBool::new(false),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        // Accept connections in the background, handling each one in its own thread.
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server_state.stopping.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let state = server_state.clone();
+                    thread::spawn(move || state.handle(stream));
+                }
+            }
+        });
+
+        let message = Data::from_text(&format!("session={}", Data::random(8).to_hex()));
+        Ok(TimingPaddingServer {
+            state,
+            address,
+            message,
+        })
+    }
+
+    /// Returns the address that the server is listening on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns a valid token, encrypted under a random IV, as the ciphertext and the IV.
+    pub fn token(&self) -> (Data, Data) {
+        let mut plaintext = self.message.bytes().to_vec();
+        plaintext.extend_from_slice(self.state.mac.tag(&self.message).unwrap().bytes());
+
+        let iv = Data::random(16);
+        let block = BlockCipher::new(Algorithms::Aes,
+                                     OperationModes::Cbc(iv.clone()),
+                                     PaddingSchemes::Pkcs7,
+                                     &self.state.key)
+            .unwrap();
+        (block.encrypt(&Data::from_bytes(plaintext)).unwrap(), iv)
+    }
+
+    /// Checks if the secret message in the token has been correctly determined.
+    pub fn check_answer(&self, message_guess: &Data) -> bool {
+        message_guess.bytes() == self.message.bytes()
+    }
+}
+
+impl Drop for TimingPaddingServer {
+    /// Stops the server from accepting connections, by setting the flag and then connecting to it
+    /// so that it notices.
+    fn drop(&mut self) {
+        self.state.stopping.store(true, Ordering::SeqCst);
+        let _ = TcpStream::connect(self.address);
+    }
+}